@@ -0,0 +1,648 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use crate::commands::print_warning;
+use crate::error::Error;
+use crate::get_library_name;
+use crate::variables::get_profile;
+
+const PACKAGE_DIRECTORIES: [&str; 8] = [
+    "lib/cmake/{name}",
+    "lib64/cmake/{name}",
+    "lib/{name}/cmake",
+    "share/cmake/{name}",
+    "share/{name}/cmake",
+    "share/{name}",
+    "{name}/cmake",
+    "cmake",
+];
+
+const CONFIGURATION_SUFFIXES: [&str; 5] = [
+    "-debug",
+    "-release",
+    "-relwithdebinfo",
+    "-minsizerel",
+    "-noconfig",
+];
+
+/// Libraries that are commonly linked through an imported target from a CMake module rather than
+/// a package config file.
+const KNOWN_SYSTEM_TARGETS: [(&str, &str); 2] = [
+    ("Threads::Threads", "pthread"),
+    ("OpenMP::OpenMP_C", "gomp"),
+];
+
+/// A command invocation read from a CMake script.
+struct CMakeCommand {
+    name: String,
+    arguments: Vec<String>,
+}
+
+/// Split a CMake script into commands and their arguments.
+///
+/// Variable references are left in place, there is no support for control flow since exported
+/// package files only ever set properties on targets.
+fn parse_commands(contents: &str) -> Vec<CMakeCommand> {
+    let chars = contents.chars().collect::<Vec<char>>();
+    let mut commands = Vec::new();
+    let mut i = 0;
+
+    // Returns the start and end of the content of a bracket argument or comment starting at
+    // `start`, along with the position after it, if there is one.
+    let bracket_end = |start: usize| -> Option<(usize, usize, usize)> {
+        if chars.get(start) != Some(&'[') {
+            return None;
+        }
+
+        let level = chars[start + 1..].iter().take_while(|x| **x == '=').count();
+
+        if chars.get(start + 1 + level) != Some(&'[') {
+            return None;
+        }
+
+        let content_start = start + level + 2;
+        let closing = format!("]{}]", "=".repeat(level)).chars().collect::<Vec<char>>();
+
+        let end = (content_start..chars.len())
+            .find(|x| chars[*x..].starts_with(&closing))
+            .unwrap_or(chars.len());
+
+        Some((content_start, end, (end + level + 2).min(chars.len())))
+    };
+
+    while i < chars.len() {
+        let c = chars[i];
+
+        if c == '#' {
+            match bracket_end(i + 1) {
+                Some((_, _, after)) => i = after,
+                None => while i < chars.len() && chars[i] != '\n' { i += 1 },
+            }
+            continue;
+        }
+
+        if !(c.is_ascii_alphabetic() || c == '_') {
+            i += 1;
+            continue;
+        }
+
+        let start = i;
+        while i < chars.len() && (chars[i].is_ascii_alphanumeric() || chars[i] == '_') {
+            i += 1;
+        }
+
+        let name = chars[start..i].iter().collect::<String>().to_lowercase();
+
+        while i < chars.len() && (chars[i] == ' ' || chars[i] == '\t') {
+            i += 1;
+        }
+
+        if chars.get(i) != Some(&'(') {
+            continue;
+        }
+
+        i += 1;
+
+        let mut arguments = Vec::new();
+        let mut depth = 0;
+
+        while i < chars.len() {
+            let c = chars[i];
+
+            if c.is_whitespace() {
+                i += 1;
+            } else if c == '#' {
+                match bracket_end(i + 1) {
+                    Some((_, _, after)) => i = after,
+                    None => while i < chars.len() && chars[i] != '\n' { i += 1 },
+                }
+            } else if c == '(' {
+                depth += 1;
+                i += 1;
+            } else if c == ')' {
+                i += 1;
+
+                if depth == 0 {
+                    break;
+                }
+
+                depth -= 1;
+            } else if let Some((content_start, end, after)) = bracket_end(i) {
+                arguments.push(chars[content_start..end].iter().collect());
+                i = after;
+            } else if c == '"' {
+                let mut argument = String::new();
+                i += 1;
+
+                while i < chars.len() && chars[i] != '"' {
+                    if chars[i] == '\\' && i + 1 < chars.len() {
+                        i += 1;
+
+                        match chars[i] {
+                            'n' => argument.push('\n'),
+                            't' => argument.push('\t'),
+                            '\n' => {},
+                            escaped => argument.push(escaped),
+                        }
+                    } else {
+                        argument.push(chars[i]);
+                    }
+
+                    i += 1;
+                }
+
+                arguments.push(argument);
+                i += 1;
+            } else {
+                let mut argument = String::new();
+
+                while i < chars.len() && !chars[i].is_whitespace() && !"()#".contains(chars[i]) {
+                    if chars[i] == '\\' && i + 1 < chars.len() {
+                        i += 1;
+                    }
+
+                    argument.push(chars[i]);
+                    i += 1;
+                }
+
+                arguments.push(argument);
+            }
+        }
+
+        commands.push(CMakeCommand {
+            name,
+            arguments,
+        });
+    }
+
+    commands
+}
+
+/// Expand `${variable}` references, unknown variables expand to an empty string.
+fn expand(
+    value: &str,
+    variables: &HashMap<String, String>,
+) -> String {
+    let mut expanded = value.to_string();
+
+    // Expand innermost references first so nested references work.
+    while let Some(start) = expanded.rfind("${") {
+        let Some(length) = expanded[start..].find('}') else {
+            break;
+        };
+
+        let name = &expanded[start + 2..start + length];
+        let value = variables.get(name).cloned().unwrap_or_default();
+
+        expanded.replace_range(start..start + length + 1, value.as_str());
+    }
+
+    expanded
+}
+
+#[derive(Default)]
+struct ImportedTarget {
+    properties: HashMap<String, String>,
+    configurations: Vec<String>,
+}
+
+impl ImportedTarget {
+
+    fn list(&self, property: &str) -> Vec<String> {
+        self.properties.get(property)
+            .map(|x| x.split(';').filter(|x| !x.is_empty()).map(|x| x.to_string()).collect())
+            .unwrap_or_default()
+    }
+
+    /// Read a per configuration property, trying the `preferred` configurations in order.
+    fn configured(
+        &self,
+        property: &str,
+        preferred: &[&str],
+    ) -> Option<String> {
+        if let Some(value) = self.properties.get(property) {
+            return Some(value.clone());
+        }
+
+        preferred.iter()
+            .map(|x| x.to_string())
+            .chain(self.configurations.iter().cloned())
+            .find_map(|x| self.properties.get(format!("{}_{}", property, x).as_str()).cloned())
+    }
+}
+
+/// Libraries and directories collected from the exported targets of a CMake package.
+#[derive(Default)]
+pub (crate) struct CMakePackage {
+    pub (crate) include_directories: Vec<PathBuf>,
+    pub (crate) library_directories: Vec<PathBuf>,
+    pub (crate) link_targets: Vec<String>,
+    pub (crate) system_link_targets: Vec<String>,
+    pub (crate) frameworks: Vec<String>,
+}
+
+fn push_unique<T: PartialEq>(values: &mut Vec<T>, value: T) {
+    if !values.contains(&value) {
+        values.push(value);
+    }
+}
+
+/// Configurations in order of preference for a cargo profile.
+fn preferred_configurations(profile: &str) -> [&'static str; 4] {
+    match profile {
+        "debug" => ["DEBUG", "RELWITHDEBINFO", "RELEASE", "MINSIZEREL"],
+        _ => ["RELEASE", "RELWITHDEBINFO", "MINSIZEREL", "DEBUG"],
+    }
+}
+
+struct PackageReader {
+    install_directory: PathBuf,
+    configurations: [&'static str; 4],
+    targets: HashMap<String, ImportedTarget>,
+    loaded_packages: Vec<String>,
+    visited: Vec<String>,
+    package: CMakePackage,
+}
+
+impl PackageReader {
+
+    fn find_package_directory(
+        &self,
+        name: &str,
+    ) -> Option<PathBuf> {
+        let names = [name.to_string(), name.to_lowercase()];
+
+        PACKAGE_DIRECTORIES.iter()
+            .flat_map(|x| names.iter().map(|name| self.install_directory.join(x.replace("{name}", name))))
+            .find(|x| {
+                x.join(format!("{}Config.cmake", name)).is_file()
+                    || x.join(format!("{}-config.cmake", name.to_lowercase())).is_file()
+            })
+    }
+
+    /// Read the exported targets of a package, returning false if the package was not found.
+    fn load_package(
+        &mut self,
+        name: &str,
+    ) -> Result<bool, Error> {
+        if self.loaded_packages.iter().any(|x| x == name) {
+            return Ok(true);
+        }
+
+        let Some(package_directory) = self.find_package_directory(name) else {
+            return Ok(false);
+        };
+
+        self.loaded_packages.push(name.to_string());
+
+        let mut files = fs::read_dir(package_directory.as_path())?
+            .filter_map(|x| x.ok())
+            .map(|x| x.path())
+            .filter(|x| x.extension().map(|x| x == "cmake").unwrap_or(false))
+            .collect::<Vec<PathBuf>>();
+
+        // Per configuration files are included by the main targets file, so read them last.
+        files.sort_by_key(|x| {
+            let stem = x.file_stem().unwrap().to_string_lossy().to_lowercase();
+            CONFIGURATION_SUFFIXES.iter().any(|suffix| stem.ends_with(suffix))
+        });
+
+        let mut variables = HashMap::new();
+
+        for file in files {
+            let contents = fs::read_to_string(file.as_path())?;
+
+            variables.insert(
+                "CMAKE_CURRENT_LIST_FILE".to_string(),
+                file.to_string_lossy().to_string()
+            );
+
+            variables.insert(
+                "CMAKE_CURRENT_LIST_DIR".to_string(),
+                package_directory.to_string_lossy().to_string()
+            );
+
+            for command in parse_commands(contents.as_str()) {
+                let arguments = command.arguments.iter()
+                    .map(|x| expand(x, &variables))
+                    .collect::<Vec<String>>();
+
+                match (command.name.as_str(), arguments.as_slice()) {
+                    ("get_filename_component", [variable, path, mode, ..])
+                        if mode == "PATH" || mode == "DIRECTORY" => {
+                        let parent = Path::new(path).parent()
+                            .map(|x| x.to_string_lossy().to_string())
+                            .unwrap_or_default();
+
+                        variables.insert(variable.clone(), parent);
+                    },
+                    ("add_library", [target, .., imported]) if imported == "IMPORTED" => {
+                        self.targets.entry(target.clone()).or_default();
+                    },
+                    ("set_target_properties", arguments) => {
+                        let Some(index) = arguments.iter().position(|x| x == "PROPERTIES") else {
+                            continue;
+                        };
+
+                        for target in arguments[..index].iter() {
+                            let target = self.targets.entry(target.clone()).or_default();
+
+                            for pair in arguments[index + 1..].chunks(2) {
+                                if let [key, value] = pair {
+                                    target.properties.insert(key.clone(), value.clone());
+                                }
+                            }
+                        }
+                    },
+                    ("set_property", [kind, target, .., property, configuration])
+                        if kind == "TARGET" && property == "IMPORTED_CONFIGURATIONS" => {
+                        self.targets.entry(target.clone()).or_default()
+                            .configurations.push(configuration.clone());
+                    },
+                    _ => {},
+                }
+            }
+        }
+
+        Ok(true)
+    }
+
+    fn add_library_file(
+        &mut self,
+        path: &Path,
+    ) {
+        let name = get_library_name(path);
+
+        // Libraries outside the install directory belong to the system.
+        if !path.starts_with(self.install_directory.as_path()) {
+            push_unique(&mut self.package.system_link_targets, name);
+            return;
+        }
+
+        if let Some(directory) = path.parent() {
+            push_unique(&mut self.package.library_directories, directory.to_path_buf());
+        }
+
+        push_unique(&mut self.package.link_targets, name);
+    }
+
+    /// Visit a link item, dependencies are added before the items that use them so the order
+    /// can be reversed into a valid static link order.
+    fn visit(
+        &mut self,
+        item: &str,
+    ) -> Result<(), Error> {
+        let item = match item.strip_prefix("$<LINK_ONLY:") {
+            Some(x) => x.strip_suffix('>').unwrap_or(x),
+            None if item.starts_with("$<") => return Ok(()),
+            None => item,
+        };
+
+        if self.visited.iter().any(|x| x == item) {
+            return Ok(());
+        }
+
+        self.visited.push(item.to_string());
+
+        if item.contains("::") && !self.targets.contains_key(item) {
+            let namespace = item.split("::").next().unwrap();
+            self.load_package(namespace)?;
+        }
+
+        if let Some(target) = self.targets.get(item) {
+            let include_directories = target.list("INTERFACE_INCLUDE_DIRECTORIES");
+            let library_directories = target.list("INTERFACE_LINK_DIRECTORIES");
+
+            let mut dependencies = target.list("INTERFACE_LINK_LIBRARIES");
+            dependencies.extend(
+                target.configured("IMPORTED_LINK_INTERFACE_LIBRARIES", &self.configurations)
+                    .map(|x| x.split(';').map(|x| x.to_string()).collect::<Vec<String>>())
+                    .unwrap_or_default()
+            );
+
+            let location = target.configured("IMPORTED_IMPLIB", &self.configurations)
+                .or_else(|| target.configured("IMPORTED_LOCATION", &self.configurations));
+
+            for directory in include_directories.iter().filter(|x| !x.starts_with("$<")) {
+                push_unique(&mut self.package.include_directories, PathBuf::from(directory));
+            }
+
+            for directory in library_directories.iter().filter(|x| !x.starts_with("$<")) {
+                push_unique(&mut self.package.library_directories, PathBuf::from(directory));
+            }
+
+            for dependency in dependencies.iter().rev() {
+                self.visit(dependency)?;
+            }
+
+            if let Some(location) = location {
+                self.add_library_file(Path::new(location.as_str()));
+            }
+        } else if let Some((_, library)) = KNOWN_SYSTEM_TARGETS.iter().find(|(x, _)| *x == item) {
+            push_unique(&mut self.package.system_link_targets, library.to_string());
+        } else if item.contains("::") {
+            print_warning(format!("Could not find imported target {}, it must be linked manually.", item));
+        } else if let Some(framework) = item.strip_prefix("-framework ") {
+            push_unique(&mut self.package.frameworks, framework.trim().to_string());
+        } else if let Some(framework) = item.strip_suffix(".framework") {
+            if let Some(framework) = Path::new(framework).file_name() {
+                push_unique(&mut self.package.frameworks, framework.to_string_lossy().to_string());
+            }
+        } else if Path::new(item).is_absolute() {
+            self.add_library_file(Path::new(item));
+        } else if let Some(library) = item.strip_prefix("-l") {
+            push_unique(&mut self.package.system_link_targets, library.to_string());
+        } else if item == "-pthread" {
+            push_unique(&mut self.package.system_link_targets, "pthread".to_string());
+        } else if !item.starts_with('-') {
+            push_unique(&mut self.package.system_link_targets, item.to_string());
+        }
+
+        Ok(())
+    }
+}
+
+/// Read the exported targets of an installed CMake package and collect everything required to
+/// link against `targets` and their transitive dependencies.
+pub (crate) fn read_package(
+    install_directory: &Path,
+    name: &str,
+    targets: &[&str],
+) -> Result<CMakePackage, Error> {
+    let mut reader = PackageReader {
+        install_directory: install_directory.to_path_buf(),
+        configurations: preferred_configurations(get_profile()?.as_str()),
+        targets: HashMap::new(),
+        loaded_packages: Vec::new(),
+        visited: Vec::new(),
+        package: CMakePackage::default(),
+    };
+
+    if !reader.load_package(name)? {
+        return Err(Error::LibraryNotFound(
+            format!("could not find CMake package {} in {:?}", name, install_directory)
+        ));
+    }
+
+    for target in targets.iter() {
+        if !reader.targets.contains_key(*target) {
+            let mut available = reader.targets.keys().cloned().collect::<Vec<String>>();
+            available.sort();
+
+            return Err(Error::LibraryNotFound(
+                format!("CMake package {} does not export {}, available targets: {:?}", name, target, available)
+            ));
+        }
+    }
+
+    for target in targets.iter().rev() {
+        reader.visit(target)?;
+    }
+
+    // Dependencies were added first, static linking needs them last.
+    reader.package.link_targets.reverse();
+    reader.package.system_link_targets.reverse();
+
+    Ok(reader.package)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_directory::TestDirectory;
+
+    const CONFIG: &str = r#"
+include("${CMAKE_CURRENT_LIST_DIR}/FooTargets.cmake")
+"#;
+
+    const TARGETS: &str = r#"
+# Compute the installation prefix relative to this file.
+get_filename_component(_IMPORT_PREFIX "${CMAKE_CURRENT_LIST_FILE}" PATH)
+get_filename_component(_IMPORT_PREFIX "${_IMPORT_PREFIX}" PATH)
+get_filename_component(_IMPORT_PREFIX "${_IMPORT_PREFIX}" PATH)
+get_filename_component(_IMPORT_PREFIX "${_IMPORT_PREFIX}" PATH)
+
+add_library(Foo::foo STATIC IMPORTED)
+
+set_target_properties(Foo::foo PROPERTIES
+  INTERFACE_INCLUDE_DIRECTORIES "${_IMPORT_PREFIX}/include;$<BUILD_INTERFACE:/build/include>"
+  INTERFACE_LINK_LIBRARIES "Foo::bar;\$<LINK_ONLY:m>;Threads::Threads;-framework CoreFoundation"
+)
+
+add_library(Foo::bar SHARED IMPORTED)
+
+set_target_properties(Foo::bar PROPERTIES
+  INTERFACE_LINK_LIBRARIES "/usr/lib/libz.so"
+)
+"#;
+
+    const TARGETS_RELEASE: &str = r#"
+set_property(TARGET Foo::foo APPEND PROPERTY IMPORTED_CONFIGURATIONS RELEASE)
+set_target_properties(Foo::foo PROPERTIES
+  IMPORTED_LOCATION_RELEASE "${_IMPORT_PREFIX}/lib/libfoo.a"
+)
+
+set_property(TARGET Foo::bar APPEND PROPERTY IMPORTED_CONFIGURATIONS RELEASE)
+set_target_properties(Foo::bar PROPERTIES
+  IMPORTED_LOCATION_RELEASE "${_IMPORT_PREFIX}/lib/libbar.so.1"
+)
+"#;
+
+    const TARGETS_DEBUG: &str = r#"
+set_property(TARGET Foo::foo APPEND PROPERTY IMPORTED_CONFIGURATIONS DEBUG)
+set_target_properties(Foo::foo PROPERTIES
+  IMPORTED_LOCATION_DEBUG "${_IMPORT_PREFIX}/lib/debug/libfood.a"
+)
+"#;
+
+    fn read_fixture(
+        name: &str,
+        profile: &str,
+    ) -> CMakePackage {
+        let install_directory = TestDirectory::new(format!("cmake-package-{}", name).as_str());
+
+        install_directory.write("lib/cmake/Foo/FooConfig.cmake", CONFIG);
+        install_directory.write("lib/cmake/Foo/FooTargets.cmake", TARGETS);
+        install_directory.write("lib/cmake/Foo/FooTargets-release.cmake", TARGETS_RELEASE);
+        install_directory.write("lib/cmake/Foo/FooTargets-debug.cmake", TARGETS_DEBUG);
+
+        let mut reader = PackageReader {
+            install_directory: install_directory.to_path_buf(),
+            configurations: preferred_configurations(profile),
+            targets: HashMap::new(),
+            loaded_packages: Vec::new(),
+            visited: Vec::new(),
+            package: CMakePackage::default(),
+        };
+
+        assert!(reader.load_package("Foo").unwrap());
+        reader.visit("Foo::foo").unwrap();
+
+        reader.package.link_targets.reverse();
+        reader.package.system_link_targets.reverse();
+
+        // Make paths relative to the install directory to compare them.
+        for directory in reader.package.include_directories.iter_mut().chain(reader.package.library_directories.iter_mut()) {
+            *directory = directory.strip_prefix(install_directory.as_path()).unwrap().to_path_buf();
+        }
+
+        reader.package
+    }
+
+    #[test]
+    fn parses_commands() {
+        let commands = parse_commands(r#"
+            # comment (with parentheses)
+            #[[ bracket
+            comment ]]
+            Set_Property(TARGET foo "quoted \"value\";with\
+ escapes" [==[bracket ]] argument]==] unquoted\ space # trailing
+              (nested) )
+            not a command
+        "#);
+
+        assert_eq!(commands.len(), 1);
+        assert_eq!(commands[0].name, "set_property");
+        assert_eq!(commands[0].arguments, vec![
+            "TARGET",
+            "foo",
+            "quoted \"value\";with escapes",
+            "bracket ]] argument",
+            "unquoted space",
+            "nested",
+        ]);
+    }
+
+    #[test]
+    fn expands_variables() {
+        let variables = HashMap::from([
+            ("prefix".to_string(), "/opt".to_string()),
+            ("name".to_string(), "prefix".to_string()),
+        ]);
+
+        assert_eq!(expand("${prefix}/lib", &variables), "/opt/lib");
+        assert_eq!(expand("${${name}}/include", &variables), "/opt/include");
+        assert_eq!(expand("${missing}/lib", &variables), "/lib");
+        assert_eq!(expand("${unterminated", &variables), "${unterminated");
+    }
+
+    #[test]
+    fn reads_release_targets() {
+        let package = read_fixture("release", "release");
+
+        assert_eq!(package.include_directories, vec![PathBuf::from("include")]);
+        assert_eq!(package.library_directories, vec![PathBuf::from("lib")]);
+        assert_eq!(package.link_targets, vec!["foo", "bar"]);
+        assert_eq!(package.system_link_targets, vec!["z", "m", "pthread"]);
+        assert_eq!(package.frameworks, vec!["CoreFoundation"]);
+    }
+
+    #[test]
+    fn reads_debug_targets() {
+        let package = read_fixture("debug", "debug");
+
+        // `Foo::bar` has no debug location and falls back to the release one.
+        assert_eq!(package.library_directories, vec![PathBuf::from("lib"), PathBuf::from("lib/debug")]);
+        assert_eq!(package.link_targets, vec!["food", "bar"]);
+    }
+}
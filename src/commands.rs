@@ -1,19 +1,128 @@
 use std::fmt::Display;
 use std::path::Path;
+use crate::error::Error;
+use crate::types::link::LinkModifiers;
+
+/// Paths are passed to cargo and cmake as text, which only works for valid UTF-8.
+pub (crate) fn utf8_path(path: &Path) -> Result<&str, Error> {
+    path.to_str()
+        .ok_or(Error::InvalidPath(path.to_path_buf()))
+}
 
-#[allow(dead_code)]
 pub (crate) fn print_warning<T: Display>(message: T) {
-    println!("cargo:warning={}", message);
+    // Cargo only shows the first line of a warning, emit one per line.
+    for line in message.to_string().lines() {
+        println!("cargo:warning={}", line);
+    }
+}
+
+pub (crate) fn rerun_if_changed(path: &Path) -> Result<(), Error> {
+    println!("cargo:rerun-if-changed={}", utf8_path(path)?);
+    Ok(())
+}
+
+pub (crate) fn rerun_if_env_changed(name: &str) {
+    println!("cargo:rerun-if-env-changed={}", name);
+}
+
+pub (crate) fn set_metadata<T: Display>(key: &str, value: T) {
+    println!("cargo:{}={}", key, value);
+}
+
+pub (crate) fn add_library_search_path(path: &Path) -> Result<(), Error> {
+    println!("cargo:rustc-link-search=native={}", utf8_path(path)?);
+    Ok(())
+}
+
+fn link_library<T: Display>(
+    kind: &str,
+    modifiers: Vec<String>,
+    lib_name: T,
+) {
+    match modifiers.is_empty() {
+        true => println!("cargo:rustc-link-lib={}={}", kind, lib_name),
+        false => println!("cargo:rustc-link-lib={}:{}={}", kind, modifiers.join(","), lib_name),
+    }
+}
+
+fn format_modifier(
+    name: &str,
+    enabled: Option<bool>,
+) -> Option<String> {
+    enabled.map(|x| format!("{}{}", if x { "+" } else { "-" }, name))
+}
+
+pub (crate) fn link_static_library<T: Display>(
+    lib_name: T,
+    modifiers: &LinkModifiers,
+) {
+    let modifiers = [
+        format_modifier("whole-archive", modifiers.whole_archive),
+        format_modifier("bundle", modifiers.bundle),
+        format_modifier("verbatim", modifiers.verbatim),
+    ];
+
+    link_library("static", modifiers.into_iter().flatten().collect(), lib_name);
+}
+
+pub (crate) fn add_rpath(path: &str) {
+    println!("cargo:rustc-link-arg=-Wl,-rpath,{}", path);
 }
 
-pub (crate) fn add_library_search_path(path: &Path) {
-    println!("cargo:rustc-link-search=native={}", path.to_str().unwrap());
+pub (crate) fn add_framework_search_path(path: &Path) -> Result<(), Error> {
+    println!("cargo:rustc-link-search=framework={}", utf8_path(path)?);
+    Ok(())
 }
 
-pub (crate) fn link_static_library<T: Display>(lib_name: T) {
-    println!("cargo:rustc-link-lib=static={}", lib_name);
+pub (crate) fn link_framework<T: Display>(
+    framework_name: T,
+    modifiers: &LinkModifiers,
+) {
+    let modifiers = [
+        format_modifier("verbatim", modifiers.verbatim),
+        format_modifier("as-needed", modifiers.as_needed),
+    ];
+
+    link_library("framework", modifiers.into_iter().flatten().collect(), framework_name);
+}
+
+pub (crate) fn link_shared_library<T: Display>(
+    lib_name: T,
+    modifiers: &LinkModifiers,
+) {
+    let modifiers = [
+        format_modifier("verbatim", modifiers.verbatim),
+        format_modifier("as-needed", modifiers.as_needed),
+    ];
+
+    link_library("dylib", modifiers.into_iter().flatten().collect(), lib_name);
 }
 
-pub (crate) fn link_shared_library<T: Display>(lib_name: T) {
-    println!("cargo:rustc-link-lib=dylib={}", lib_name);
-}
\ No newline at end of file
+/// Link static libraries inside a linker group, which searches them repeatedly until no new
+/// symbols are resolved so libraries that depend on each other can be linked in any order.
+pub (crate) fn add_link_group(archives: &[&Path]) -> Result<(), Error> {
+    println!("cargo:rustc-link-arg=-Wl,--start-group");
+
+    for archive in archives.iter() {
+        println!("cargo:rustc-link-arg={}", utf8_path(archive)?);
+    }
+
+    println!("cargo:rustc-link-arg=-Wl,--end-group");
+    Ok(())
+}
+
+#[cfg(test)]
+#[cfg(unix)]
+mod tests {
+    use super::*;
+    use std::ffi::OsStr;
+    use std::os::unix::ffi::OsStrExt;
+
+    #[test]
+    fn rejects_paths_that_are_not_utf8() {
+        let path = Path::new(OsStr::from_bytes(b"/tmp/\xff"));
+
+        assert_eq!(utf8_path(Path::new("/tmp/lib")).unwrap(), "/tmp/lib");
+        assert!(matches!(utf8_path(path), Err(Error::InvalidPath(x)) if x == path));
+    }
+}
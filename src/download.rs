@@ -0,0 +1,211 @@
+use std::{env, fs};
+use std::fs::File;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use crate::error::Error;
+use crate::hash::Sha256;
+use crate::process::run;
+
+/// Archive formats that can be extracted, by file extension.
+const ARCHIVE_EXTENSIONS: [&str; 5] = [
+    ".tar.gz",
+    ".tgz",
+    ".tar.xz",
+    ".tar.zst",
+    ".zip",
+];
+
+/// Extension of the archive a URL points to, ignoring any query string or fragment.
+pub (crate) fn archive_extension(url: &str) -> Result<&'static str, Error> {
+    let path = url.split(['?', '#']).next().unwrap_or_default().to_lowercase();
+
+    ARCHIVE_EXTENSIONS.iter()
+        .find(|x| path.ends_with(*x))
+        .copied()
+        .ok_or(Error::ExtractFailed(format!(
+            "{} is not a supported archive, expected one of {}", url, ARCHIVE_EXTENSIONS.join(", ")
+        )))
+}
+
+fn is_installed(program: &str) -> bool {
+    let path = env::var_os("PATH").unwrap_or_default();
+    let file_name = format!("{}{}", program, env::consts::EXE_SUFFIX);
+
+    env::split_paths(&path).any(|x| x.join(file_name.as_str()).is_file())
+}
+
+/// Programs needed to download and extract an archive, and what they are needed for.
+fn required_programs(
+    extension: &str,
+    downloading: bool,
+) -> Vec<(&'static str, String)> {
+    let mut programs = Vec::new();
+
+    if downloading {
+        programs.push(("curl", "download archives".to_string()));
+    }
+
+    match extension {
+        ".zip" => programs.push(("unzip", "extract .zip archives".to_string())),
+        _ => programs.push(("tar", format!("extract {} archives", extension))),
+    }
+
+    // tar runs these to decompress the archive.
+    match extension {
+        ".tar.xz" => programs.push(("xz", "extract .tar.xz archives".to_string())),
+        ".tar.zst" => programs.push(("zstd", "extract .tar.zst archives".to_string())),
+        _ => {},
+    }
+
+    programs
+}
+
+/// Check that the programs to download and extract an archive are installed, so that a missing
+/// one is reported before spending time on the download.
+pub (crate) fn check_programs(
+    extension: &str,
+    downloading: bool,
+) -> Result<(), Error> {
+    match required_programs(extension, downloading).into_iter().find(|(x, _)| !is_installed(x)) {
+        Some((program, purpose)) => Err(Error::ProgramNotFound {
+            program: program.to_string(),
+            purpose,
+        }),
+        None => Ok(()),
+    }
+}
+
+/// Download a file with curl, following redirects.
+///
+/// The file is written next to `path` first so an interrupted download is never mistaken for a
+/// complete one.
+pub (crate) fn download(
+    url: &str,
+    path: &Path,
+) -> Result<(), Error> {
+    let mut partial_path = path.as_os_str().to_owned();
+    partial_path.push(".part");
+
+    run(Command::new("curl")
+        .arg("--fail")
+        .arg("--location")
+        .arg("--silent")
+        .arg("--show-error")
+        .arg("--output")
+        .arg(partial_path.as_os_str())
+        .arg(url)
+    ).map_err(Error::DownloadFailed)?;
+
+    fs::rename(partial_path, path)?;
+    Ok(())
+}
+
+/// SHA-256 of a file as a lowercase hex string.
+pub (crate) fn sha256_file(path: &Path) -> Result<String, Error> {
+    let mut file = File::open(path)?;
+    let mut hash = Sha256::new();
+    let mut buffer = vec![0; 64 * 1024];
+
+    loop {
+        let length = file.read(buffer.as_mut_slice())?;
+
+        if length == 0 {
+            break;
+        }
+
+        hash.update(&buffer[..length]);
+    }
+
+    Ok(hash.finish())
+}
+
+/// Extract an archive into `destination`, which must not exist.
+///
+/// Release archives usually put everything in a single directory such as `zlib-1.3.1`, it is
+/// stripped so that the contents end up directly in `destination`.
+pub (crate) fn extract(
+    archive: &Path,
+    destination: &Path,
+) -> Result<(), Error> {
+    let mut extract_directory = destination.as_os_str().to_owned();
+    extract_directory.push(".extract");
+    let extract_directory = PathBuf::from(extract_directory);
+
+    if extract_directory.exists() {
+        fs::remove_dir_all(extract_directory.as_path())?;
+    }
+
+    fs::create_dir_all(extract_directory.as_path())?;
+
+    let mut command = match archive_extension(archive.to_string_lossy().as_ref())? {
+        ".zip" => {
+            let mut command = Command::new("unzip");
+            command.arg("-q").arg(archive).arg("-d").arg(extract_directory.as_path());
+            command
+        },
+        // tar detects the compression itself, xz and zstd need their command to be installed.
+        _ => {
+            let mut command = Command::new("tar");
+            command.arg("-xf").arg(archive).arg("-C").arg(extract_directory.as_path());
+            command
+        },
+    };
+
+    run(&mut command)
+        .map_err(|e| Error::ExtractFailed(e.to_string()))?;
+
+    let entries = fs::read_dir(extract_directory.as_path())?
+        .filter_map(|x| x.ok())
+        .map(|x| x.path())
+        .collect::<Vec<PathBuf>>();
+
+    match entries.as_slice() {
+        [root] if root.is_dir() => {
+            fs::rename(root, destination)?;
+            fs::remove_dir(extract_directory.as_path())?;
+        },
+        _ => fs::rename(extract_directory.as_path(), destination)?,
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_archive_extensions() {
+        assert_eq!(archive_extension("https://example.com/zlib-1.3.1.tar.gz").unwrap(), ".tar.gz");
+        assert_eq!(archive_extension("https://example.com/zlib.TGZ").unwrap(), ".tgz");
+        assert_eq!(archive_extension("https://example.com/zlib.tar.zst?raw=true").unwrap(), ".tar.zst");
+        assert_eq!(archive_extension("https://example.com/zlib.zip#readme").unwrap(), ".zip");
+        assert_eq!(archive_extension("/tmp/zlib.tar.xz").unwrap(), ".tar.xz");
+
+        assert!(archive_extension("https://example.com/zlib.tar").is_err());
+        assert!(archive_extension("https://example.com/zlib.rar").is_err());
+        assert!(archive_extension("https://example.com/download?file=zlib.tar.gz#x.txt").is_err());
+    }
+
+    #[test]
+    fn lists_required_programs() {
+        let programs = |extension, downloading| required_programs(extension, downloading)
+            .into_iter()
+            .map(|(x, _)| x)
+            .collect::<Vec<_>>();
+
+        assert_eq!(programs(".tar.gz", true), ["curl", "tar"]);
+        assert_eq!(programs(".tar.xz", true), ["curl", "tar", "xz"]);
+        assert_eq!(programs(".tar.zst", true), ["curl", "tar", "zstd"]);
+        assert_eq!(programs(".zip", true), ["curl", "unzip"]);
+        assert_eq!(programs(".zip", false), ["unzip"]);
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn finds_installed_programs() {
+        assert!(is_installed("sh"));
+        assert!(!is_installed("bind-builder-missing-command"));
+    }
+}
@@ -0,0 +1,219 @@
+use std::fmt::{Display, Formatter};
+use std::path::PathBuf;
+use std::{fmt, io};
+
+/// A command that could not be run or exited unsuccessfully.
+#[derive(Debug)]
+pub struct CommandError {
+    /// The full command line that was run.
+    pub command: String,
+
+    /// The directory the command was run in.
+    pub directory: PathBuf,
+
+    /// The exit code of the command, `None` if it could not be run or was terminated by a signal.
+    pub status: Option<i32>,
+
+    /// Captured standard error of the command, or the reason it could not be run.
+    pub stderr: String,
+}
+
+impl Display for CommandError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self.status {
+            Some(code) => write!(f, "`{}` in {:?} exited with status {}", self.command, self.directory, code)?,
+            None => write!(f, "`{}` in {:?} did not complete", self.command, self.directory)?,
+        }
+
+        if !self.stderr.trim().is_empty() {
+            write!(f, "\n{}", self.stderr.trim_end())?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Errors that can occur while fetching, building or binding a native library.
+#[derive(Debug)]
+pub enum Error {
+    /// A git operation could not be run or did not succeed.
+    GitFailed(CommandError),
+
+    /// A cloned repository is not at the commit it was pinned to.
+    RevisionMismatch {
+        url: String,
+        expected: String,
+        actual: String,
+    },
+
+    /// A revision to pin a clone to is not a full commit hash.
+    InvalidRevision(String),
+
+    /// An archive could not be downloaded.
+    DownloadFailed(CommandError),
+
+    /// A downloaded archive does not have the expected SHA-256 checksum.
+    ChecksumMismatch {
+        url: String,
+        expected: String,
+        actual: String,
+    },
+
+    /// An archive is not in a supported format or could not be extracted.
+    ExtractFailed(String),
+
+    /// A program needed to download or extract an archive is not installed.
+    ProgramNotFound {
+        program: String,
+        purpose: String,
+    },
+
+    /// Patches were given for a project that can not be patched.
+    PatchFailed(String),
+
+    /// The `cmake` configure or build step did not succeed.
+    CMakeConfigureFailed(String),
+
+    /// The CMake File API reply could not be read.
+    CMakeFileApiFailed(String),
+
+    /// The `cmake --install` step could not be run or did not succeed.
+    InstallFailed(CommandError),
+
+    /// A library, or a directory that was expected to contain one, could not be found.
+    LibraryNotFound(String),
+
+    /// A link target could not be found in any of the library directories.
+    LinkTargetNotFound {
+        name: String,
+        searched: Vec<PathBuf>,
+    },
+
+    /// A version requirement could not be parsed.
+    InvalidVersion(String),
+
+    /// A library was found but its version does not satisfy the requirement.
+    VersionMismatch {
+        name: String,
+        version: String,
+        required: String,
+    },
+
+    /// None of the sources given to a `LibraryResolver` produced a library.
+    ResolutionFailed {
+        name: String,
+        attempts: Vec<(String, Error)>,
+    },
+
+    /// An environment variable cargo sets for build scripts is missing or malformed.
+    InvalidEnvironment(String),
+
+    /// A path could not be passed to cargo or cmake because it is not valid UTF-8.
+    InvalidPath(PathBuf),
+
+    /// A file system operation failed.
+    Io(io::Error),
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::GitFailed(message) => write!(f, "git failed: {}", message),
+            Error::RevisionMismatch { url, expected, actual } => write!(
+                f, "{} resolved to revision {}, expected {}", url, actual, expected
+            ),
+            Error::InvalidRevision(revision) => write!(
+                f, "invalid revision {}, expected a full commit hash", revision
+            ),
+            Error::DownloadFailed(message) => write!(f, "download failed: {}", message),
+            Error::ChecksumMismatch { url, expected, actual } => write!(
+                f, "{} has checksum {}, expected {}", url, actual, expected
+            ),
+            Error::ExtractFailed(message) => write!(f, "extracting archive failed: {}", message),
+            Error::ProgramNotFound { program, purpose } => write!(
+                f, "{} is required to {} but was not found in PATH", program, purpose
+            ),
+            Error::PatchFailed(message) => write!(f, "patching failed: {}", message),
+            Error::CMakeConfigureFailed(message) => write!(f, "cmake failed: {}", message),
+            Error::CMakeFileApiFailed(message) => write!(f, "cmake file api failed: {}", message),
+            Error::InstallFailed(message) => write!(f, "cmake install failed: {}", message),
+            Error::LibraryNotFound(message) => write!(f, "library not found: {}", message),
+            Error::LinkTargetNotFound { name, searched } => {
+                write!(f, "could not find library {}, searched:", name)?;
+
+                for path in searched {
+                    write!(f, "\n  {:?}", path)?;
+                }
+
+                Ok(())
+            },
+            Error::InvalidVersion(requirement) => write!(f, "invalid version requirement: {}", requirement),
+            Error::VersionMismatch { name, version, required } => write!(
+                f, "{} version {} does not satisfy requirement {}", name, version, required
+            ),
+            Error::ResolutionFailed { name, attempts } => {
+                write!(f, "could not resolve {}", name)?;
+
+                for (source, error) in attempts {
+                    write!(f, "\n  {}: {}", source, error)?;
+                }
+
+                Ok(())
+            },
+            Error::InvalidEnvironment(message) => write!(f, "invalid build environment: {}", message),
+            Error::InvalidPath(path) => write!(f, "path is not valid UTF-8: {:?}", path),
+            Error::Io(error) => write!(f, "io error: {}", error),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(error: io::Error) -> Self {
+        Error::Io(error)
+    }
+}
+
+/// Convert a `Result` into its value, panicking with the error message on failure.
+///
+/// Used by the panicking entry points that wrap their `try_` counterparts.
+pub (crate) fn unwrap_or_panic<T>(result: Result<T, Error>) -> T {
+    result.unwrap_or_else(|error| panic!("{}", error))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unwraps_values() {
+        assert_eq!(unwrap_or_panic(Ok::<u32, Error>(1)), 1);
+    }
+
+    #[test]
+    #[should_panic(expected = "invalid build environment: OUT_DIR is not set")]
+    fn panics_with_error_message() {
+        unwrap_or_panic::<()>(Err(Error::InvalidEnvironment("OUT_DIR is not set".to_string())));
+    }
+
+    #[test]
+    fn lists_searched_paths() {
+        let error = Error::LinkTargetNotFound {
+            name: "foo".to_string(),
+            searched: vec![PathBuf::from("/opt/lib/libfoo.a"), PathBuf::from("/opt/lib/libfoo.so")],
+        };
+
+        assert_eq!(
+            error.to_string(),
+            "could not find library foo, searched:\n  \"/opt/lib/libfoo.a\"\n  \"/opt/lib/libfoo.so\"",
+        );
+    }
+}
@@ -0,0 +1,185 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use crate::error::Error;
+use crate::json::Json;
+use crate::types::build_info::{BuildInfo, BuildTarget, TargetKind};
+
+const CLIENT_NAME: &str = "client-bind-builder";
+
+fn api_directory(build_directory: &Path) -> PathBuf {
+    build_directory.join(".cmake").join("api").join("v1")
+}
+
+fn read_json(path: &Path) -> Result<Json, Error> {
+    let contents = fs::read_to_string(path)?;
+
+    Json::parse(contents.as_str())
+        .map_err(|e| Error::CMakeFileApiFailed(format!("could not parse {:?}: {}", path, e)))
+}
+
+fn invalid_reply(message: &str) -> Error {
+    Error::CMakeFileApiFailed(format!("invalid reply, {}", message))
+}
+
+/// Ask CMake to write a codemodel reply the next time the project is configured.
+pub (crate) fn write_query(build_directory: &Path) -> Result<(), Error> {
+    let query_directory = api_directory(build_directory)
+        .join("query")
+        .join(CLIENT_NAME);
+
+    fs::create_dir_all(query_directory.as_path())?;
+    fs::write(query_directory.join("codemodel-v2"), "")?;
+
+    Ok(())
+}
+
+/// Whether CMake has written a reply to our query.
+pub (crate) fn has_reply(build_directory: &Path) -> bool {
+    find_codemodel(build_directory).is_ok()
+}
+
+/// Find the codemodel reply referenced by the latest reply index.
+fn find_codemodel(build_directory: &Path) -> Result<PathBuf, Error> {
+    let reply_directory = api_directory(build_directory).join("reply");
+
+    let index = fs::read_dir(reply_directory.as_path())
+        .map_err(|_| Error::CMakeFileApiFailed(format!("no reply found in {:?}", reply_directory)))?
+        .filter_map(|x| x.ok())
+        .map(|x| x.path())
+        .filter(|x| x.file_name().unwrap().to_string_lossy().starts_with("index-"))
+        .max()
+        .ok_or(Error::CMakeFileApiFailed(format!("no reply index found in {:?}", reply_directory)))?;
+
+    let index = read_json(index.as_path())?;
+
+    let json_file = index.get("reply")
+        .and_then(|x| x.get(CLIENT_NAME))
+        .and_then(|x| x.get("codemodel-v2"))
+        .and_then(|x| x.get("jsonFile"))
+        .and_then(|x| x.as_str())
+        .ok_or(invalid_reply("no codemodel in reply index"))?;
+
+    Ok(reply_directory.join(json_file))
+}
+
+/// Read the targets of the configuration matching `build_type` from the codemodel reply.
+pub (crate) fn read_build_info(
+    build_directory: &Path,
+    build_type: &str,
+) -> Result<BuildInfo, Error> {
+    let codemodel_path = find_codemodel(build_directory)?;
+    let reply_directory = codemodel_path.parent().unwrap();
+    let codemodel = read_json(codemodel_path.as_path())?;
+
+    let build_directory = codemodel.get("paths")
+        .and_then(|x| x.get("build"))
+        .and_then(|x| x.as_str())
+        .map(PathBuf::from)
+        .unwrap_or(build_directory.to_path_buf());
+
+    let source_directory = codemodel.get("paths")
+        .and_then(|x| x.get("source"))
+        .and_then(|x| x.as_str())
+        .map(PathBuf::from)
+        .unwrap_or(build_directory.clone());
+
+    // Single configuration generators have one unnamed or matching configuration, multi
+    // configuration generators have one per build type.
+    let configurations = codemodel.get("configurations")
+        .map(|x| x.elements())
+        .unwrap_or_default();
+
+    let configuration = configurations.iter()
+        .find(|x| x.get("name").and_then(|x| x.as_str()).map(|x| x.eq_ignore_ascii_case(build_type)).unwrap_or(false))
+        .or(configurations.first())
+        .ok_or(invalid_reply("no configurations in codemodel"))?;
+
+    let target_references = configuration.get("targets")
+        .map(|x| x.elements())
+        .unwrap_or_default();
+
+    // Dependencies are given by id, map them back to target names.
+    let target_name = |id: &str| -> Option<String> {
+        target_references.iter()
+            .find(|x| x.get("id").and_then(|x| x.as_str()) == Some(id))
+            .and_then(|x| x.get("name"))
+            .and_then(|x| x.as_str())
+            .map(|x| x.to_string())
+    };
+
+    let mut targets = Vec::new();
+
+    for target_reference in target_references {
+        let json_file = target_reference.get("jsonFile")
+            .and_then(|x| x.as_str())
+            .ok_or(invalid_reply("target without jsonFile"))?;
+
+        let target = read_json(reply_directory.join(json_file).as_path())?;
+
+        let name = target.get("name")
+            .and_then(|x| x.as_str())
+            .ok_or(invalid_reply("target without name"))?;
+
+        let Some(kind) = target.get("type").and_then(|x| x.as_str()).and_then(TargetKind::from_file_api) else {
+            continue;
+        };
+
+        let artifacts = target.get("artifacts")
+            .map(|x| x.elements())
+            .unwrap_or_default()
+            .iter()
+            .filter_map(|x| x.get("path").and_then(|x| x.as_str()))
+            .map(|x| build_directory.join(x))
+            .collect();
+
+        let mut include_directories = Vec::new();
+
+        for compile_group in target.get("compileGroups").map(|x| x.elements()).unwrap_or_default() {
+            for include in compile_group.get("includes").map(|x| x.elements()).unwrap_or_default() {
+                let Some(path) = include.get("path").and_then(|x| x.as_str()) else {
+                    continue;
+                };
+
+                let path = source_directory.join(path);
+
+                if !include_directories.contains(&path) {
+                    include_directories.push(path);
+                }
+            }
+        }
+
+        let dependencies = target.get("dependencies")
+            .map(|x| x.elements())
+            .unwrap_or_default()
+            .iter()
+            .filter_map(|x| x.get("id").and_then(|x| x.as_str()))
+            .filter_map(target_name)
+            .collect();
+
+        let link_libraries = target.get("link")
+            .and_then(|x| x.get("commandFragments"))
+            .map(|x| x.elements())
+            .unwrap_or_default()
+            .iter()
+            .filter(|x| x.get("role").and_then(|x| x.as_str()) == Some("libraries"))
+            .filter_map(|x| x.get("fragment").and_then(|x| x.as_str()))
+            .map(|x| x.to_string())
+            .collect();
+
+        targets.push(BuildTarget {
+            name: name.to_string(),
+            kind,
+
+            artifacts,
+            include_directories,
+
+            dependencies,
+            link_libraries,
+        });
+    }
+
+    Ok(BuildInfo {
+        build_directory,
+        targets,
+    })
+}
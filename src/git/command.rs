@@ -0,0 +1,190 @@
+use std::path::Path;
+use std::process::{Command, Stdio};
+use crate::error::Error;
+use crate::process::{run, run_output};
+use crate::types::clone_options::{CloneOptions, Submodules};
+
+fn git(directory: &Path) -> Command {
+    let mut command = Command::new("git");
+    command.current_dir(directory);
+    command
+}
+
+/// Create an empty repository with `url` as its `origin` remote.
+pub (crate) fn init(
+    directory: &Path,
+    url: &str,
+) -> Result<(), Error> {
+    run(git(directory)
+        .arg("init")
+    ).map_err(Error::GitFailed)?;
+
+    run(git(directory)
+        .arg("remote")
+        .arg("add")
+        .arg("origin")
+        .arg(url)
+    ).map_err(Error::GitFailed)
+}
+
+/// Fetch a tag, branch or commit from the `origin` remote.
+pub (crate) fn fetch(
+    directory: &Path,
+    refspec: &str,
+    options: &CloneOptions,
+) -> Result<(), Error> {
+    let mut command = git(directory);
+    command.arg("fetch");
+
+    if let Some(depth) = options.depth {
+        command.arg(format!("--depth={}", depth));
+    }
+
+    if let Some(filter) = &options.filter {
+        // Objects left out by the filter are fetched later on demand, which git only does from
+        // remotes marked as promisors.
+        run(git(directory)
+            .arg("config")
+            .arg("remote.origin.promisor")
+            .arg("true")
+        ).map_err(Error::GitFailed)?;
+
+        run(git(directory)
+            .arg("config")
+            .arg("remote.origin.partialclonefilter")
+            .arg(filter)
+        ).map_err(Error::GitFailed)?;
+
+        command.arg(format!("--filter={}", filter));
+    }
+
+    run(command
+        .arg("origin")
+        .arg(refspec)
+    ).map_err(Error::GitFailed)
+}
+
+/// Limit the working tree to the given directories, or check out every file if there are none.
+pub (crate) fn sparse_checkout(
+    directory: &Path,
+    paths: &[String],
+) -> Result<(), Error> {
+    if paths.is_empty() {
+        return run(git(directory)
+            .arg("sparse-checkout")
+            .arg("disable")
+        ).map_err(Error::GitFailed);
+    }
+
+    run(git(directory)
+        .arg("sparse-checkout")
+        .arg("set")
+        .arg("--cone")
+        .args(paths)
+    ).map_err(Error::GitFailed)
+}
+
+/// Reset the working tree to a revision, discarding any local changes.
+///
+/// Use `FETCH_HEAD` for the revision that was just fetched, fetching a tag by name does not
+/// always create it locally.
+pub (crate) fn reset(
+    directory: &Path,
+    revision: &str,
+) -> Result<(), Error> {
+    run(git(directory)
+        .arg("reset")
+        .arg("--hard")
+        .arg(revision)
+    ).map_err(Error::GitFailed)
+}
+
+/// Remove every file that is not tracked, including ignored ones, so that files created by
+/// patches do not outlive them. Nested repositories such as submodules are kept.
+pub (crate) fn clean(directory: &Path) -> Result<(), Error> {
+    run(git(directory)
+        .arg("clean")
+        .arg("-fdx")
+    ).map_err(Error::GitFailed)
+}
+
+/// Initialize and update the selected submodules, recursively.
+pub (crate) fn update_submodules(
+    directory: &Path,
+    options: &CloneOptions,
+) -> Result<(), Error> {
+    let paths = match &options.submodules {
+        Submodules::All => &Vec::new(),
+        Submodules::None => return Ok(()),
+        Submodules::Paths(paths) => paths,
+    };
+
+    let mut command = git(directory);
+
+    command
+        .arg("submodule")
+        .arg("update")
+        .arg("--init")
+        .arg("--recursive");
+
+    if let Some(depth) = options.depth {
+        command.arg(format!("--depth={}", depth));
+    }
+
+    if let Some(filter) = &options.filter {
+        command.arg(format!("--filter={}", filter));
+    }
+
+    run(command
+        .arg("--")
+        .args(paths)
+    ).map_err(Error::GitFailed)
+}
+
+/// Full SHA of the commit checked out.
+pub (crate) fn head_revision(directory: &Path) -> Result<String, Error> {
+    let revision = run_output(git(directory)
+        .arg("rev-parse")
+        .arg("HEAD")
+    ).map_err(Error::GitFailed)?;
+
+    Ok(revision.trim().to_string())
+}
+
+fn apply(directory: &Path) -> Command {
+    let mut command = git(directory);
+
+    // Paths in the patch are relative to the directory even when it is inside another repository.
+    if let Some(parent) = directory.parent() {
+        command.env("GIT_CEILING_DIRECTORIES", parent);
+    }
+
+    command.arg("apply");
+    command
+}
+
+/// Apply a patch to the files of a directory without committing it, skipping it if it is already
+/// applied. This accepts plain unified diffs as well as patches made with `git format-patch`.
+pub (crate) fn apply_patch(
+    directory: &Path,
+    patch: &Path,
+) -> Result<(), Error> {
+    // The patch can be reversed if and only if it is already applied.
+    let applied = apply(directory)
+        .arg("--reverse")
+        .arg("--check")
+        .arg(patch)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()?
+        .success();
+
+    if applied {
+        return Ok(());
+    }
+
+    run(apply(directory)
+        .arg(patch)
+    ).map_err(Error::GitFailed)
+}
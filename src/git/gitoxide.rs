@@ -0,0 +1,667 @@
+use std::fs;
+use std::num::NonZeroU32;
+use std::path::Path;
+use std::sync::atomic::AtomicBool;
+use gix::bstr::{BStr, ByteSlice};
+use gix::index::entry::{Flags, Mode};
+use gix::progress::Discard;
+use gix::refs::transaction::{PreviousValue, RefEdit};
+use gix::remote::fetch::{Shallow, Tags};
+use gix::remote::{ref_map, Direction};
+use gix::worktree::stack::state::attributes::Source;
+use gix::ObjectId;
+use crate::commands::print_warning;
+use crate::error::{CommandError, Error};
+use crate::git::patch;
+use crate::types::clone_options::{CloneOptions, Submodules};
+
+/// Branches fetched when a commit can not be fetched by its id, the same as `git remote add`.
+const DEFAULT_REFSPEC: &str = "+refs/heads/*:refs/remotes/origin/*";
+
+/// File in `.git/info` with the directories of a sparse checkout, one per line. This is not the
+/// `sparse-checkout` file of git, which would only take effect with the git command.
+const SPARSE_PATHS: &str = "bind-builder-sparse-paths";
+
+fn git_error(
+    directory: &Path,
+    operation: &str,
+    error: impl Into<Box<dyn std::error::Error + Send + Sync>>,
+) -> Error {
+    let error = error.into();
+    let mut message = error.to_string();
+    let mut source = error.source();
+
+    while let Some(cause) = source {
+        message.push_str(format!("\ncaused by: {}", cause).as_str());
+        source = cause.source();
+    }
+
+    Error::GitFailed(CommandError {
+        command: format!("gix {}", operation),
+        directory: directory.to_path_buf(),
+        status: None,
+        stderr: message,
+    })
+}
+
+/// Create an empty repository with `url` as its `origin` remote.
+pub (crate) fn init(
+    directory: &Path,
+    url: &str,
+) -> Result<(), Error> {
+    let failed = |error| git_error(directory, "init", error);
+
+    let repository = gix::init(directory).map_err(failed)?;
+
+    let mut remote = repository.remote_at(url)
+        .and_then(|x| x.with_refspecs(Some(DEFAULT_REFSPEC), Direction::Fetch))
+        .map_err(failed)?;
+
+    let config_path = repository.git_dir().join("config");
+    let mut config = gix::config::File::from_path_no_includes(config_path.clone(), gix::config::Source::Local)
+        .map_err(failed)?;
+
+    remote.save_as_to("origin", &mut config).map_err(failed)?;
+    fs::write(config_path, config.to_bstring())?;
+
+    Ok(())
+}
+
+/// Fetch a tag, branch or commit from the `origin` remote.
+///
+/// Partial clone filters are not supported, every object is fetched instead.
+pub (crate) fn fetch(
+    directory: &Path,
+    refspec: &str,
+    options: &CloneOptions,
+) -> Result<(), Error> {
+    let operation = format!("fetch origin {}", refspec);
+    let failed = |error| git_error(directory, operation.as_str(), error);
+
+    if let Some(filter) = &options.filter {
+        print_warning(format!("gitoxide does not support the partial clone filter {}, fetching every object", filter));
+    }
+
+    let repository = gix::open(directory).map_err(failed)?;
+
+    let mut remote = repository.find_remote("origin")
+        .map_err(failed)?
+        .with_fetch_tags(Tags::None);
+
+    // Only fetch what was asked for, like `git fetch origin <refspec>`.
+    remote.replace_refspecs(Some(refspec), Direction::Fetch).map_err(failed)?;
+
+    let url = remote.url(Direction::Fetch)
+        .map(|x| x.to_bstring().to_string())
+        .unwrap_or_default();
+
+    let mut prepare = remote.connect(Direction::Fetch)
+        .and_then(|x| x.prepare_fetch(Discard, ref_map::Options::default()))
+        .map_err(failed)?;
+
+    if let Some(depth) = options.depth.and_then(NonZeroU32::new) {
+        prepare = prepare.with_shallow(Shallow::DepthAtRemote(depth));
+    }
+
+    let outcome = prepare.receive(Discard, &AtomicBool::new(false)).map_err(failed)?;
+
+    let id = outcome.ref_map.mappings.iter()
+        .find_map(|x| x.remote.as_id())
+        .ok_or_else(|| git_error(directory, operation.as_str(), format!("couldn't find remote ref {}", refspec)))?;
+
+    fs::write(
+        repository.git_dir().join("FETCH_HEAD"),
+        format!("{}\t\t'{}' of {}\n", id, refspec, url),
+    )?;
+
+    Ok(())
+}
+
+/// Whether a file is checked out by a cone mode sparse checkout of `paths`: files in the root
+/// directory, in the directories of `paths` and directly in their parent directories.
+fn is_in_cone(
+    file: &BStr,
+    paths: &[String],
+) -> bool {
+    let Some((parent, _)) = file.rsplit_once_str("/") else {
+        return true;
+    };
+
+    paths.iter().any(|path| {
+        let path = path.trim_matches('/').as_bytes();
+
+        file.starts_with(path) && file.get(path.len()) == Some(&b'/')
+            || path.starts_with(parent) && path.get(parent.len()) == Some(&b'/')
+    })
+}
+
+/// Limit the working tree to the given directories, or check out every file if there are none.
+///
+/// This takes effect on the next `reset`.
+pub (crate) fn sparse_checkout(
+    directory: &Path,
+    paths: &[String],
+) -> Result<(), Error> {
+    let failed = |error| git_error(directory, "sparse-checkout set", error);
+
+    let repository = gix::open(directory).map_err(failed)?;
+    let sparse_paths_path = repository.git_dir().join("info").join(SPARSE_PATHS);
+
+    if paths.is_empty() {
+        if sparse_paths_path.exists() {
+            fs::remove_file(sparse_paths_path)?;
+        }
+
+        return Ok(());
+    }
+
+    fs::create_dir_all(repository.git_dir().join("info"))?;
+    fs::write(sparse_paths_path, paths.join("\n") + "\n")?;
+
+    Ok(())
+}
+
+fn sparse_paths(repository: &gix::Repository) -> Result<Vec<String>, Error> {
+    let path = repository.git_dir().join("info").join(SPARSE_PATHS);
+
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+
+    Ok(fs::read_to_string(path)?
+        .lines()
+        .map(|x| x.trim().to_string())
+        .filter(|x| !x.is_empty())
+        .collect())
+}
+
+/// Remove a file and any parent directories it leaves empty, up to `root`.
+fn remove_file(
+    root: &Path,
+    file: &Path,
+) -> Result<(), Error> {
+    if !file.is_file() && !file.is_symlink() {
+        return Ok(());
+    }
+
+    fs::remove_file(file)?;
+
+    for directory in file.ancestors().skip(1).take_while(|x| *x != root) {
+        if fs::remove_dir(directory).is_err() {
+            break;
+        }
+    }
+
+    Ok(())
+}
+
+/// Reset the working tree to a revision, discarding any local changes.
+///
+/// Use `FETCH_HEAD` for the revision that was just fetched, otherwise this only accepts full
+/// commit ids.
+pub (crate) fn reset(
+    directory: &Path,
+    revision: &str,
+) -> Result<(), Error> {
+    let operation = format!("reset --hard {}", revision);
+    let failed = |error| git_error(directory, operation.as_str(), error);
+
+    let mut repository = gix::open(directory).map_err(failed)?;
+
+    let id = match revision {
+        "FETCH_HEAD" => fs::read_to_string(repository.git_dir().join("FETCH_HEAD"))?
+            .split_whitespace()
+            .next()
+            .map(|x| x.to_string()),
+        _ => Some(revision.to_string()),
+    };
+
+    let id = id.and_then(|x| ObjectId::from_hex(x.as_bytes()).ok())
+        .ok_or_else(|| git_error(directory, operation.as_str(), format!("{} is not a commit id", revision)))?;
+
+    let (commit, tree) = repository.find_object(id)
+        .and_then(|x| x.peel_to_commit())
+        .and_then(|x| Ok((x.id, x.tree_id()?.detach())))
+        .map_err(failed)?;
+
+    let sparse_paths = sparse_paths(&repository)?;
+
+    let mut index = repository.index_from_tree(&tree).map_err(failed)?;
+
+    for (entry, path) in index.entries_mut_with_paths() {
+        if !sparse_paths.is_empty() && !is_in_cone(path, sparse_paths.as_slice()) {
+            entry.flags.insert(Flags::SKIP_WORKTREE | Flags::EXTENDED);
+        }
+    }
+
+    let work_directory = repository.workdir()
+        .ok_or_else(|| git_error(directory, operation.as_str(), "repository has no working tree"))?
+        .to_path_buf();
+
+    // Checking out only writes files, so remove the ones that are no longer part of the tree or
+    // are now outside of the sparse checkout. Untracked files are kept, the same as git.
+    if let Some(previous_index) = repository.try_index().map_err(failed)? {
+        for entry in previous_index.entries() {
+            let path = entry.path(&previous_index);
+
+            let is_kept = index.entry_by_path(path)
+                .is_some_and(|x| !x.flags.contains(Flags::SKIP_WORKTREE));
+
+            if entry.mode != Mode::COMMIT && !is_kept {
+                remove_file(work_directory.as_path(), work_directory.join(path.to_path_lossy()).as_path())?;
+            }
+        }
+    }
+
+    let mut checkout_options = repository.checkout_options(Source::IdMapping).map_err(failed)?;
+    checkout_options.overwrite_existing = true;
+
+    let objects = repository.objects.clone().into_arc()?;
+
+    let outcome = gix::worktree::state::checkout(
+        &mut index,
+        work_directory.as_path(),
+        objects,
+        &Discard,
+        &Discard,
+        &AtomicBool::new(false),
+        checkout_options,
+    ).map_err(failed)?;
+
+    if let Some(error) = outcome.errors.first() {
+        return Err(git_error(directory, operation.as_str(), format!("{}: {}", error.path, error.error)));
+    }
+
+    if let Some(collision) = outcome.collisions.first() {
+        return Err(git_error(directory, operation.as_str(), format!("{}: {:?}", collision.path, collision.error_kind)));
+    }
+
+    index.write(Default::default()).map_err(failed)?;
+
+    // A fresh repository may not have a committer for the reflog.
+    repository.committer_or_set_generic_fallback().map_err(failed)?;
+
+    let head = "HEAD".try_into().map_err(|e| git_error(directory, operation.as_str(), e))?;
+
+    repository.edit_reference(RefEdit::update(
+        head,
+        commit,
+        PreviousValue::Any,
+        format!("reset: moving to {}", revision),
+    )).map_err(failed)?;
+
+    Ok(())
+}
+
+fn clean_directory(
+    root: &Path,
+    directory: &Path,
+    index: &gix::index::State,
+) -> Result<(), Error> {
+    for entry in fs::read_dir(directory)? {
+        let path = entry?.path();
+        let relative_path = path.strip_prefix(root).unwrap()
+            .to_string_lossy()
+            .replace('\\', "/");
+
+        if path.is_dir() && !path.is_symlink() {
+            // Skip the repository itself and nested ones, such as submodules.
+            if relative_path == ".git" || path.join(".git").exists() {
+                continue;
+            }
+
+            clean_directory(root, path.as_path(), index)?;
+
+            if fs::read_dir(path.as_path())?.next().is_none() {
+                fs::remove_dir(path.as_path())?;
+            }
+        } else if index.entry_by_path(relative_path.as_str().into()).is_none() {
+            fs::remove_file(path.as_path())?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Remove every file that is not tracked, including ignored ones, so that files created by
+/// patches do not outlive them. Nested repositories such as submodules are kept.
+pub (crate) fn clean(directory: &Path) -> Result<(), Error> {
+    let failed = |error| git_error(directory, "clean -fdx", error);
+
+    let repository = gix::open(directory).map_err(failed)?;
+    let index = repository.index_or_empty().map_err(failed)?;
+
+    let work_directory = repository.workdir()
+        .ok_or_else(|| git_error(directory, "clean -fdx", "repository has no working tree"))?;
+
+    clean_directory(work_directory, work_directory, &index)
+}
+
+/// Resolve a submodule URL relative to the URL of its superproject, like `../other.git`.
+fn resolve_url(
+    superproject_url: &str,
+    url: &str,
+) -> String {
+    if !url.starts_with("./") && !url.starts_with("../") {
+        return url.to_string();
+    }
+
+    let mut resolved = superproject_url.trim_end_matches('/').to_string();
+
+    for component in url.split('/') {
+        match component {
+            "." => {},
+            ".." => match resolved.rfind(['/', ':']) {
+                // Keep the separator of scp-like URLs such as `git@example.com:repo`.
+                Some(end) if resolved[end..].starts_with(':') => resolved.truncate(end + 1),
+                Some(end) => resolved.truncate(end),
+                None => resolved.clear(),
+            },
+            component => {
+                if !resolved.ends_with(':') {
+                    resolved.push('/');
+                }
+
+                resolved.push_str(component);
+            },
+        }
+    }
+
+    resolved
+}
+
+/// Initialize and update the selected submodules, recursively.
+///
+/// Submodule repositories are created inside their directory rather than in the `.git`
+/// directory of the superproject.
+pub (crate) fn update_submodules(
+    directory: &Path,
+    options: &CloneOptions,
+) -> Result<(), Error> {
+    let failed = |error| git_error(directory, "submodule update", error);
+
+    let paths = match &options.submodules {
+        Submodules::All => &Vec::new(),
+        Submodules::None => return Ok(()),
+        Submodules::Paths(paths) => paths,
+    };
+
+    let repository = gix::open(directory).map_err(failed)?;
+    let index = repository.index_or_empty().map_err(failed)?;
+
+    let Some(submodules) = repository.submodules().map_err(failed)? else {
+        return Ok(());
+    };
+
+    let superproject_url = repository.find_remote("origin")
+        .ok()
+        .and_then(|x| x.url(Direction::Fetch).map(|x| x.to_bstring().to_string()))
+        .unwrap_or_default();
+
+    // Nested submodules are always updated, the same as `git submodule update --recursive`.
+    let mut nested_options = options.clone();
+    nested_options.submodules = Submodules::All;
+
+    for submodule in submodules {
+        let path = submodule.path().map_err(failed)?;
+
+        if !paths.is_empty() && !paths.iter().any(|x| x.trim_end_matches('/').as_bytes() == path.as_slice()) {
+            continue;
+        }
+
+        // Submodules outside of a sparse checkout are not checked out.
+        let Some(entry) = index.entry_by_path(path.as_bstr()) else {
+            continue;
+        };
+
+        if entry.mode != Mode::COMMIT || entry.flags.contains(Flags::SKIP_WORKTREE) {
+            continue;
+        }
+
+        let url = submodule.url().map_err(failed)?.to_bstring().to_string();
+        let url = resolve_url(superproject_url.as_str(), url.as_str());
+
+        let submodule_directory = directory.join(path.to_path_lossy());
+        let commit = entry.id.to_string();
+
+        if !submodule_directory.join(".git").exists() {
+            fs::create_dir_all(submodule_directory.as_path())?;
+            init(submodule_directory.as_path(), url.as_str())?;
+        }
+
+        // Not every server allows fetching a commit by its id, git also falls back to fetching
+        // every branch in that case.
+        if fetch(submodule_directory.as_path(), commit.as_str(), options).is_err() {
+            fetch(submodule_directory.as_path(), DEFAULT_REFSPEC, options)?;
+        }
+
+        reset(submodule_directory.as_path(), commit.as_str())?;
+        update_submodules(submodule_directory.as_path(), &nested_options)?;
+    }
+
+    Ok(())
+}
+
+/// Full SHA of the commit checked out.
+pub (crate) fn head_revision(directory: &Path) -> Result<String, Error> {
+    let failed = |error| git_error(directory, "rev-parse HEAD", error);
+
+    let repository = gix::open(directory).map_err(failed)?;
+    let id = repository.head_id().map_err(failed)?;
+
+    Ok(id.to_string())
+}
+
+/// Apply a patch to the files of a directory without committing it, skipping it if it is already
+/// applied. This accepts plain unified diffs as well as patches made with `git format-patch`.
+pub (crate) fn apply_patch(
+    directory: &Path,
+    patch: &Path,
+) -> Result<(), Error> {
+    let contents = fs::read(patch)?;
+
+    patch::apply_patch(directory, contents.as_slice())
+        .map_err(|e| git_error(directory, format!("apply {}", patch.display()).as_str(), e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::process::Command;
+    use crate::test_directory::TestDirectory;
+
+    /// Run the git command to create fixtures, the code under test does not use it.
+    fn git(
+        directory: &Path,
+        arguments: &[&str],
+    ) -> String {
+        let output = Command::new("git")
+            .args(["-c", "user.name=bind-builder", "-c", "user.email=bind-builder@example.com"])
+            .args(["-c", "protocol.file.allow=always", "-c", "init.defaultBranch=main"])
+            .args(arguments)
+            .current_dir(directory)
+            .output()
+            .unwrap();
+
+        assert!(output.status.success(), "git {:?}: {}", arguments, String::from_utf8_lossy(&output.stderr));
+        String::from_utf8_lossy(&output.stdout).trim().to_string()
+    }
+
+    fn file_url(path: &Path) -> String {
+        format!("file://{}", path.display())
+    }
+
+    /// Create bare repositories in `root` and return the URL of the superproject. It has the tags
+    /// `v1` and `v2` and two submodules, `third_party/sub` and `third_party/unused`.
+    fn remote(root: &TestDirectory) -> String {
+        let sub = root.join("sub");
+        root.write("sub/sub.c", "int sub;\n");
+        git(sub.as_path(), &["init", "-q"]);
+        git(sub.as_path(), &["add", "."]);
+        git(sub.as_path(), &["commit", "-q", "-m", "Add sub"]);
+        git(root.as_path(), &["clone", "-q", "--bare", "sub", "sub.git"]);
+
+        let work = root.join("work");
+        root.write("work/CMakeLists.txt", "project(foo)\n");
+        root.write("work/src/lib/a.c", "int a;\n");
+        root.write("work/src/app/main.c", "int main;\n");
+        root.write("work/docs/index.md", "# foo\n");
+        git(work.as_path(), &["init", "-q"]);
+
+        let sub_url = file_url(root.join("sub.git").as_path());
+        git(work.as_path(), &["submodule", "add", "-q", sub_url.as_str(), "third_party/sub"]);
+        git(work.as_path(), &["submodule", "add", "-q", sub_url.as_str(), "third_party/unused"]);
+        git(work.as_path(), &["add", "."]);
+        git(work.as_path(), &["commit", "-q", "-m", "First"]);
+        git(work.as_path(), &["tag", "v1"]);
+
+        root.write("work/src/lib/a.c", "int a = 2;\n");
+        root.write("work/src/lib/b.c", "int b;\n");
+        git(work.as_path(), &["rm", "-q", "src/app/main.c"]);
+        git(work.as_path(), &["add", "."]);
+        git(work.as_path(), &["commit", "-q", "-m", "Second"]);
+        git(work.as_path(), &["tag", "v2"]);
+
+        git(root.as_path(), &["clone", "-q", "--bare", "work", "remote.git"]);
+        file_url(root.join("remote.git").as_path())
+    }
+
+    /// Clone the same way as `CMakeBuilder::try_clone_with_options`.
+    fn clone(
+        directory: &Path,
+        url: &str,
+        tag: &str,
+        options: &CloneOptions,
+    ) {
+        if !directory.exists() {
+            fs::create_dir_all(directory).unwrap();
+            init(directory, url).unwrap();
+        }
+
+        fetch(directory, tag, options).unwrap();
+        sparse_checkout(directory, options.sparse_paths.as_slice()).unwrap();
+        reset(directory, "FETCH_HEAD").unwrap();
+        clean(directory).unwrap();
+        update_submodules(directory, options).unwrap();
+    }
+
+    fn read(
+        directory: &Path,
+        name: &str,
+    ) -> Option<String> {
+        fs::read_to_string(directory.join(name)).ok()
+    }
+
+    #[test]
+    fn matches_cone_patterns() {
+        let paths = ["src/lib".to_string(), "docs/".to_string()];
+
+        assert!(is_in_cone("CMakeLists.txt".into(), &paths));
+        assert!(is_in_cone("src/lib/a/b.c".into(), &paths));
+        assert!(is_in_cone("src/CMakeLists.txt".into(), &paths));
+        assert!(is_in_cone("docs/index.md".into(), &paths));
+
+        assert!(!is_in_cone("src/app/main.c".into(), &paths));
+        assert!(!is_in_cone("src/library/a.c".into(), &paths));
+        assert!(!is_in_cone("tests/a.c".into(), &paths));
+    }
+
+    #[test]
+    fn clones_and_switches_tags() {
+        let root = TestDirectory::new("gitoxide-tags");
+        let url = remote(&root);
+        let directory = root.join("clone");
+        let options = CloneOptions::new().submodules(Submodules::None).get();
+
+        clone(directory.as_path(), url.as_str(), "v1", &options);
+
+        assert_eq!(head_revision(directory.as_path()).unwrap(), git(root.join("work").as_path(), &["rev-parse", "v1^{commit}"]));
+        assert_eq!(read(directory.as_path(), "src/lib/a.c").as_deref(), Some("int a;\n"));
+        assert_eq!(read(directory.as_path(), "src/app/main.c").as_deref(), Some("int main;\n"));
+
+        clone(directory.as_path(), url.as_str(), "v2", &options);
+
+        assert_eq!(head_revision(directory.as_path()).unwrap(), git(root.join("work").as_path(), &["rev-parse", "v2^{commit}"]));
+        assert_eq!(read(directory.as_path(), "src/lib/a.c").as_deref(), Some("int a = 2;\n"));
+        assert_eq!(read(directory.as_path(), "src/lib/b.c").as_deref(), Some("int b;\n"));
+        assert!(!directory.join("src/app").exists());
+
+        clone(directory.as_path(), url.as_str(), "v1", &options);
+
+        assert_eq!(read(directory.as_path(), "src/app/main.c").as_deref(), Some("int main;\n"));
+        assert!(!directory.join("src/lib/b.c").exists());
+    }
+
+    #[test]
+    fn clones_sparse_paths_without_history() {
+        let root = TestDirectory::new("gitoxide-sparse");
+        let url = remote(&root);
+        let directory = root.join("clone");
+        let options = CloneOptions::new().depth(1).sparse_path("src/lib").get();
+
+        clone(directory.as_path(), url.as_str(), "v1", &options);
+
+        assert!(directory.join("CMakeLists.txt").is_file());
+        assert!(directory.join("src/lib/a.c").is_file());
+        assert!(!directory.join("src/app").exists());
+        assert!(!directory.join("docs").exists());
+
+        // Submodules outside of the sparse checkout are skipped.
+        assert!(!directory.join("third_party/sub/sub.c").exists());
+
+        clone(directory.as_path(), url.as_str(), "v1", &CloneOptions::new().submodules(Submodules::None).get());
+
+        assert!(directory.join("src/app/main.c").is_file());
+        assert!(directory.join("docs/index.md").is_file());
+    }
+
+    #[test]
+    fn clones_selected_submodules() {
+        let root = TestDirectory::new("gitoxide-submodules");
+        let url = remote(&root);
+        let directory = root.join("clone");
+        let options = CloneOptions::new()
+            .submodules(Submodules::Paths(vec!["third_party/sub".to_string()]))
+            .get();
+
+        clone(directory.as_path(), url.as_str(), "v1", &options);
+
+        assert_eq!(read(directory.as_path(), "third_party/sub/sub.c").as_deref(), Some("int sub;\n"));
+        assert!(!directory.join("third_party/unused/sub.c").exists());
+
+        clone(directory.as_path(), url.as_str(), "v1", &CloneOptions::new());
+
+        assert!(directory.join("third_party/unused/sub.c").is_file());
+    }
+
+    #[test]
+    fn cleans_untracked_files() {
+        let root = TestDirectory::new("gitoxide-clean");
+        let url = remote(&root);
+        let directory = root.join("clone");
+
+        clone(directory.as_path(), url.as_str(), "v1", &CloneOptions::new());
+
+        root.write("clone/untracked.txt", "");
+        root.write("clone/new/directory/file.c", "");
+        root.write("clone/third_party/sub/untracked.txt", "");
+        fs::write(directory.join("src/lib/a.c"), "int patched;\n").unwrap();
+
+        clone(directory.as_path(), url.as_str(), "v1", &CloneOptions::new());
+
+        assert!(!directory.join("untracked.txt").exists());
+        assert!(!directory.join("new").exists());
+        assert_eq!(read(directory.as_path(), "src/lib/a.c").as_deref(), Some("int a;\n"));
+
+        // Submodules are repositories of their own and are left alone.
+        assert!(directory.join("third_party/sub/untracked.txt").exists());
+    }
+
+    #[test]
+    fn resolves_relative_urls() {
+        assert_eq!(resolve_url("https://example.com/org/repo.git", "../other.git"), "https://example.com/org/other.git");
+        assert_eq!(resolve_url("https://example.com/org/repo/", "./sub"), "https://example.com/org/repo/sub");
+        assert_eq!(resolve_url("git@example.com:org/repo", "../../other/repo"), "git@example.com:other/repo");
+        assert_eq!(resolve_url("/tmp/repo", "https://example.com/sub"), "https://example.com/sub");
+    }
+}
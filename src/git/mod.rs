@@ -0,0 +1,16 @@
+// Every git operation used to clone projects goes through this module, so that the backend can
+// be changed without touching the builders. By default it runs the git command, which inherits the
+// git configuration and credentials from the system. The `gix` feature uses gitoxide instead, so
+// that git does not need to be installed.
+
+#[cfg(not(feature = "gix"))]
+mod command;
+#[cfg(feature = "gix")]
+mod gitoxide;
+#[cfg(feature = "gix")]
+mod patch;
+
+#[cfg(not(feature = "gix"))]
+pub (crate) use command::*;
+#[cfg(feature = "gix")]
+pub (crate) use gitoxide::*;
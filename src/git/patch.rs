@@ -0,0 +1,508 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+// gitoxide can not apply patches, so this replaces `git apply` for the unified diffs written by
+// `diff -u`, `git diff` and `git format-patch`. Hunks must match exactly but may have moved.
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Line {
+    Context,
+    Removed,
+    Added,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+struct Hunk {
+    old_start: usize,
+    new_start: usize,
+    lines: Vec<(Line, Vec<u8>)>,
+
+    // Set by `\ No newline at end of file` after the last old or new line.
+    old_missing_newline: bool,
+    new_missing_newline: bool,
+}
+
+impl Hunk {
+
+    fn lines_of(&self, excluded: Line) -> Vec<&[u8]> {
+        self.lines.iter()
+            .filter(|(kind, _)| *kind != excluded)
+            .map(|(_, text)| text.as_slice())
+            .collect()
+    }
+}
+
+/// Changes to a single file, paths are `None` for files that are created or deleted.
+#[derive(Clone, Debug, PartialEq)]
+struct FilePatch {
+    old_path: Option<String>,
+    new_path: Option<String>,
+    hunks: Vec<Hunk>,
+}
+
+impl FilePatch {
+
+    fn name(&self) -> &str {
+        self.new_path.as_ref()
+            .or(self.old_path.as_ref())
+            .map(|x| x.as_str())
+            .unwrap_or("/dev/null")
+    }
+
+    fn reverse(&self) -> FilePatch {
+        let hunks = self.hunks.iter()
+            .map(|hunk| Hunk {
+                old_start: hunk.new_start,
+                new_start: hunk.old_start,
+                lines: hunk.lines.iter()
+                    .map(|(kind, text)| match kind {
+                        Line::Context => (Line::Context, text.clone()),
+                        Line::Removed => (Line::Added, text.clone()),
+                        Line::Added => (Line::Removed, text.clone()),
+                    })
+                    .collect(),
+                old_missing_newline: hunk.new_missing_newline,
+                new_missing_newline: hunk.old_missing_newline,
+            })
+            .collect();
+
+        FilePatch {
+            old_path: self.new_path.clone(),
+            new_path: self.old_path.clone(),
+            hunks,
+        }
+    }
+
+    /// Apply the hunks in order to the contents of the file.
+    fn apply(
+        &self,
+        contents: &[u8],
+    ) -> Result<Vec<u8>, String> {
+        let mut lines: Vec<&[u8]> = split_lines(contents);
+        let mut missing_newline = !contents.is_empty() && !contents.ends_with(b"\n");
+
+        // Lines added or removed by earlier hunks, or that they were moved by.
+        let mut shift: isize = 0;
+
+        for hunk in self.hunks.iter() {
+            let old = hunk.lines_of(Line::Added);
+            let new = hunk.lines_of(Line::Removed);
+
+            // Lines are numbered from one, and hunks that only insert lines insert them after the
+            // line they start at.
+            let start = match old.is_empty() {
+                true => hunk.old_start,
+                false => hunk.old_start.saturating_sub(1),
+            };
+
+            let expected = (start as isize + shift).clamp(0, lines.len() as isize) as usize;
+
+            let position = find_lines(lines.as_slice(), old.as_slice(), expected)
+                .filter(|x| !hunk.old_missing_newline || (x + old.len() == lines.len() && missing_newline))
+                .ok_or_else(|| format!("patch does not apply to {}", self.name()))?;
+
+            if position + old.len() == lines.len() {
+                missing_newline = hunk.new_missing_newline;
+            }
+
+            shift += position as isize - start as isize + new.len() as isize - old.len() as isize;
+            lines.splice(position..position + old.len(), new);
+        }
+
+        let mut result = lines.join(&b'\n');
+
+        if !lines.is_empty() && !missing_newline {
+            result.push(b'\n');
+        }
+
+        Ok(result)
+    }
+}
+
+/// Lines without their line endings, a final line ending does not start another line.
+fn split_lines(data: &[u8]) -> Vec<&[u8]> {
+    let data = data.strip_suffix(b"\n").unwrap_or(data);
+
+    match data.is_empty() {
+        true => Vec::new(),
+        false => data.split(|x| *x == b'\n').collect(),
+    }
+}
+
+/// Position of `needle` in `lines` closest to `expected`.
+fn find_lines(
+    lines: &[&[u8]],
+    needle: &[&[u8]],
+    expected: usize,
+) -> Option<usize> {
+    let matches = |position: usize| lines.get(position..position + needle.len())
+        .is_some_and(|x| x == needle);
+
+    (0..=lines.len())
+        .flat_map(|distance| [expected.checked_add(distance), expected.checked_sub(distance)])
+        .flatten()
+        .find(|x| matches(*x))
+}
+
+/// Path of a `---` or `+++` line, without the `a/` or `b/` prefix.
+fn parse_path(line: &[u8]) -> Option<String> {
+    // Timestamps written by `diff -u` are separated by a tab.
+    let path = line.split(|x| *x == b'\t').next().unwrap_or_default();
+    let path = String::from_utf8_lossy(path);
+    let path = path.trim_end().trim_matches('"');
+
+    if path == "/dev/null" {
+        return None;
+    }
+
+    // The same as `git apply -p1`.
+    match path.split_once('/') {
+        Some((_, path)) => Some(path.to_string()),
+        None => Some(path.to_string()),
+    }
+}
+
+fn parse_range(range: &str) -> Option<(usize, usize)> {
+    let (start, count) = range.split_once(',').unwrap_or((range, "1"));
+    Some((start.parse().ok()?, count.parse().ok()?))
+}
+
+/// Start and length of the old and new lines in a `@@ -1,2 +1,3 @@` line.
+fn parse_hunk_header(line: &[u8]) -> Option<((usize, usize), (usize, usize))> {
+    let line = std::str::from_utf8(line).ok()?;
+    let mut ranges = line.strip_prefix("@@ ")?.split(' ');
+
+    let old = parse_range(ranges.next()?.strip_prefix('-')?)?;
+    let new = parse_range(ranges.next()?.strip_prefix('+')?)?;
+
+    Some((old, new))
+}
+
+fn parse(data: &[u8]) -> Result<Vec<FilePatch>, String> {
+    let lines = split_lines(data);
+    let mut files = Vec::new();
+    let mut index = 0;
+
+    while index < lines.len() {
+        let line = lines[index];
+
+        if line.starts_with(b"GIT binary patch") || line.starts_with(b"Binary files ") {
+            return Err("binary patches are not supported".to_string());
+        }
+
+        // Anything else between files, such as the message of a patch made with
+        // `git format-patch` or the `diff --git` headers, is skipped.
+        let is_file_header = line.starts_with(b"--- ")
+            && lines.get(index + 1).is_some_and(|x| x.starts_with(b"+++ "));
+
+        if !is_file_header {
+            index += 1;
+            continue;
+        }
+
+        let mut file = FilePatch {
+            old_path: parse_path(&line[4..]),
+            new_path: parse_path(&lines[index + 1][4..]),
+            hunks: Vec::new(),
+        };
+
+        index += 2;
+
+        while let Some(((old_start, mut old_count), (new_start, mut new_count))) = lines.get(index)
+            .and_then(|x| parse_hunk_header(x)) {
+
+            let mut hunk = Hunk {
+                old_start,
+                new_start,
+                lines: Vec::new(),
+                old_missing_newline: false,
+                new_missing_newline: false,
+            };
+
+            index += 1;
+
+            while old_count > 0 || new_count > 0 || lines.get(index).is_some_and(|x| x.starts_with(b"\\")) {
+                let line = lines.get(index)
+                    .ok_or_else(|| format!("patch for {} ends in the middle of a hunk", file.name()))?;
+
+                index += 1;
+
+                let kind = match line.first() {
+                    // Some editors strip the space of empty context lines.
+                    Some(b' ') | None => Line::Context,
+                    Some(b'-') => Line::Removed,
+                    Some(b'+') => Line::Added,
+                    Some(b'\\') => {
+                        // `\ No newline at end of file` applies to the line before it.
+                        match hunk.lines.last() {
+                            Some((Line::Context, _)) => {
+                                hunk.old_missing_newline = true;
+                                hunk.new_missing_newline = true;
+                            },
+                            Some((Line::Removed, _)) => hunk.old_missing_newline = true,
+                            Some((Line::Added, _)) => hunk.new_missing_newline = true,
+                            None => {},
+                        }
+
+                        continue;
+                    },
+                    _ => return Err(format!("patch for {} has a malformed hunk", file.name())),
+                };
+
+                let counts = match kind {
+                    Line::Context => (old_count.checked_sub(1), new_count.checked_sub(1)),
+                    Line::Removed => (old_count.checked_sub(1), Some(new_count)),
+                    Line::Added => (Some(old_count), new_count.checked_sub(1)),
+                };
+
+                let (Some(old), Some(new)) = counts else {
+                    return Err(format!("patch for {} has a hunk longer than its header", file.name()));
+                };
+
+                old_count = old;
+                new_count = new;
+
+                hunk.lines.push((kind, line.get(1..).unwrap_or_default().to_vec()));
+            }
+
+            file.hunks.push(hunk);
+        }
+
+        files.push(file);
+    }
+
+    Ok(files)
+}
+
+fn read(
+    changes: &BTreeMap<PathBuf, Option<Vec<u8>>>,
+    path: &Path,
+) -> Option<Vec<u8>> {
+    match changes.get(path) {
+        Some(contents) => contents.clone(),
+        None => fs::read(path).ok(),
+    }
+}
+
+/// New contents of every file changed by the patches, `None` for files that are deleted.
+fn changes(
+    directory: &Path,
+    files: &[FilePatch],
+) -> Result<BTreeMap<PathBuf, Option<Vec<u8>>>, String> {
+    let mut changes = BTreeMap::new();
+
+    for file in files.iter() {
+        let old_path = file.old_path.as_ref().map(|x| directory.join(x));
+        let new_path = file.new_path.as_ref().map(|x| directory.join(x));
+
+        let contents = match old_path.as_ref() {
+            Some(path) => read(&changes, path)
+                .ok_or_else(|| format!("{} does not exist", file.name()))?,
+            None if new_path.as_ref().is_some_and(|x| read(&changes, x).is_some()) => {
+                return Err(format!("{} already exists", file.name()));
+            },
+            None => Vec::new(),
+        };
+
+        let contents = file.apply(contents.as_slice())?;
+
+        if let Some(path) = old_path {
+            changes.insert(path, None);
+        }
+
+        match new_path {
+            Some(path) => {
+                changes.insert(path, Some(contents));
+            },
+            None if !contents.is_empty() => {
+                return Err(format!("patch does not delete all of {}", file.name()));
+            },
+            None => {},
+        }
+    }
+
+    Ok(changes)
+}
+
+/// Apply a patch to the files of a directory, skipping it if it is already applied.
+///
+/// Either every file is changed or none are.
+pub (super) fn apply_patch(
+    directory: &Path,
+    patch: &[u8],
+) -> Result<(), String> {
+    let files = parse(patch)?;
+
+    if files.is_empty() {
+        return Err("no changes found in patch".to_string());
+    }
+
+    // The patch can be reversed if and only if it is already applied.
+    let reversed = files.iter()
+        .rev()
+        .map(|x| x.reverse())
+        .collect::<Vec<_>>();
+
+    if changes(directory, reversed.as_slice()).is_ok() {
+        return Ok(());
+    }
+
+    for (path, contents) in changes(directory, files.as_slice())? {
+        let result = match contents {
+            Some(contents) => path.parent()
+                .map_or(Ok(()), fs::create_dir_all)
+                .and_then(|_| fs::write(path.as_path(), contents)),
+            None => fs::remove_file(path.as_path()),
+        };
+
+        result.map_err(|e| format!("could not write {:?}: {}", path, e))?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_directory::TestDirectory;
+
+    const CHANGE: &[u8] = b"\
+From 1234 Mon Sep 17 00:00:00 2001
+Subject: [PATCH] Change things
+
+---
+ foo.txt | 3 ++-
+ 1 file changed
+
+diff --git a/foo.txt b/foo.txt
+index 1111111..2222222 100644
+--- a/foo.txt
++++ b/foo.txt
+@@ -2,3 +2,4 @@ header
+ two
+-three
++THREE
++three and a half
+ four
+--- a/bar.txt\t2024-01-01 00:00:00
++++ b/bar.txt\t2024-01-01 00:00:00
+@@ -1 +1 @@
+-bar
+\\ No newline at end of file
++bar
+--
+2.43.0
+";
+
+    const CREATE: &[u8] = b"\
+--- /dev/null
++++ b/new/baz.txt
+@@ -0,0 +1,2 @@
++baz
++
+";
+
+    const DELETE: &[u8] = b"\
+--- a/bar.txt
++++ /dev/null
+@@ -1 +0,0 @@
+-bar
+";
+
+    fn directory(name: &str) -> TestDirectory {
+        let directory = TestDirectory::new(format!("patch-{}", name).as_str());
+
+        directory.write("foo.txt", "one\ntwo\nthree\nfour\nfive\n");
+        directory.write("bar.txt", "bar");
+
+        directory
+    }
+
+    fn read(
+        directory: &Path,
+        name: &str,
+    ) -> String {
+        fs::read_to_string(directory.join(name)).unwrap()
+    }
+
+    #[test]
+    fn parses_patches() {
+        let files = parse(CHANGE).unwrap();
+
+        assert_eq!(files.len(), 2);
+
+        assert_eq!(files[0].old_path.as_deref(), Some("foo.txt"));
+        assert_eq!(files[0].new_path.as_deref(), Some("foo.txt"));
+        assert_eq!(files[0].hunks[0].old_start, 2);
+        assert_eq!(files[0].hunks[0].lines.len(), 5);
+
+        assert_eq!(files[1].old_path.as_deref(), Some("bar.txt"));
+        assert!(files[1].hunks[0].old_missing_newline);
+        assert!(!files[1].hunks[0].new_missing_newline);
+
+        let files = parse(CREATE).unwrap();
+
+        assert_eq!(files[0].old_path, None);
+        assert_eq!(files[0].hunks[0].lines[1], (Line::Added, Vec::new()));
+
+        let files = parse(b"--- a/foo\n+++ b/foo\n@@ -1,2 +1,2 @@\n\n-one\n+two\n").unwrap();
+
+        assert_eq!(files[0].hunks[0].lines[0], (Line::Context, Vec::new()));
+    }
+
+    #[test]
+    fn rejects_malformed_patches() {
+        assert!(parse(b"--- a/foo\n+++ b/foo\n@@ -1,2 +1,2 @@\n one\n").is_err());
+        assert!(parse(b"--- a/foo\n+++ b/foo\n@@ -1 +1 @@\n one\n two\n").is_ok());
+        assert!(parse(b"--- a/foo\n+++ b/foo\n@@ -1 +1 @@\n*one\n").is_err());
+        assert!(parse(b"diff --git a/foo b/foo\nGIT binary patch\n").is_err());
+    }
+
+    #[test]
+    fn applies_moved_hunks() {
+        let patch = parse(CHANGE).unwrap();
+
+        assert_eq!(
+            patch[0].apply(b"zero\none\ntwo\nthree\nfour\n").unwrap(),
+            b"zero\none\ntwo\nTHREE\nthree and a half\nfour\n",
+        );
+
+        assert!(patch[0].apply(b"one\ntwo\n3\nfour\n").is_err());
+        assert_eq!(patch[1].apply(b"bar").unwrap(), b"bar\n");
+        assert!(patch[1].apply(b"bar\n").is_err());
+        assert_eq!(patch[1].reverse().apply(b"bar\n").unwrap(), b"bar");
+    }
+
+    #[test]
+    fn applies_patches_once() {
+        let directory = directory("once");
+
+        apply_patch(directory.as_path(), CHANGE).unwrap();
+        apply_patch(directory.as_path(), CHANGE).unwrap();
+
+        assert_eq!(read(directory.as_path(), "foo.txt"), "one\ntwo\nTHREE\nthree and a half\nfour\nfive\n");
+        assert_eq!(read(directory.as_path(), "bar.txt"), "bar\n");
+
+        apply_patch(directory.as_path(), CREATE).unwrap();
+        apply_patch(directory.as_path(), CREATE).unwrap();
+
+        assert_eq!(read(directory.as_path(), "new/baz.txt"), "baz\n\n");
+
+        apply_patch(directory.as_path(), DELETE).unwrap();
+        apply_patch(directory.as_path(), DELETE).unwrap();
+
+        assert!(!directory.join("bar.txt").exists());
+    }
+
+    #[test]
+    fn applies_all_files_or_none() {
+        let directory = directory("atomic");
+        fs::write(directory.join("bar.txt"), "baz").unwrap();
+
+        assert!(apply_patch(directory.as_path(), CHANGE).is_err());
+        assert_eq!(read(directory.as_path(), "foo.txt"), "one\ntwo\nthree\nfour\nfive\n");
+
+        assert!(apply_patch(directory.as_path(), b"not a patch\n").is_err());
+    }
+}
@@ -0,0 +1,72 @@
+/// Match a path against a glob pattern.
+///
+/// `*` matches anything except `/`, `**` matches anything including `/` and `?` matches a single
+/// character. Patterns without a `/` match the file name at any depth, the same as `.gitignore`.
+pub (crate) fn matches(
+    pattern: &str,
+    path: &str,
+) -> bool {
+    let pattern = match pattern.contains('/') {
+        true => pattern.trim_start_matches('/').to_string(),
+        false => format!("**/{}", pattern),
+    };
+
+    matches_from(pattern.as_bytes(), path.as_bytes())
+}
+
+fn matches_from(
+    pattern: &[u8],
+    path: &[u8],
+) -> bool {
+    match pattern {
+        [] => path.is_empty(),
+        [b'*', b'*', b'/', rest @ ..] => {
+            // Zero or more whole directories.
+            matches_from(rest, path) || (0..path.len())
+                .filter(|x| path[*x] == b'/')
+                .any(|x| matches_from(rest, &path[x + 1..]))
+        },
+        [b'*', b'*', rest @ ..] => (0..=path.len()).any(|x| matches_from(rest, &path[x..])),
+        [b'*', rest @ ..] => (0..=path.len())
+            .take_while(|x| *x == 0 || path[x - 1] != b'/')
+            .any(|x| matches_from(rest, &path[x..])),
+        [b'?', rest @ ..] => !path.is_empty() && path[0] != b'/' && matches_from(rest, &path[1..]),
+        [c, rest @ ..] => path.first() == Some(c) && matches_from(rest, &path[1..]),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matches_file_names_at_any_depth() {
+        assert!(matches("*.c", "main.c"));
+        assert!(matches("*.c", "src/deep/main.c"));
+        assert!(!matches("*.c", "main.cpp"));
+        assert!(!matches("*.c", "src.c/main.h"));
+        assert!(matches("CMakeLists.txt", "src/CMakeLists.txt"));
+    }
+
+    #[test]
+    fn matches_paths() {
+        assert!(matches("src/*.c", "src/main.c"));
+        assert!(!matches("src/*.c", "src/deep/main.c"));
+        assert!(!matches("src/*.c", "other/src/main.c"));
+        assert!(matches("/src/*.c", "src/main.c"));
+        assert!(matches("src/**/*.c", "src/main.c"));
+        assert!(matches("src/**/*.c", "src/a/b/main.c"));
+        assert!(matches("src/**", "src/a/b/main.c"));
+        assert!(matches("**/test/*", "a/test/b"));
+        assert!(!matches("src/**/*.c", "srcx/main.c"));
+    }
+
+    #[test]
+    fn matches_single_characters() {
+        assert!(matches("file?.txt", "file1.txt"));
+        assert!(!matches("file?.txt", "file.txt"));
+        assert!(!matches("a?b", "a/b"));
+        assert!(matches("*", "anything"));
+        assert!(!matches("", "anything"));
+    }
+}
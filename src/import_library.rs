@@ -0,0 +1,172 @@
+use std::fs;
+use std::path::Path;
+use crate::error::Error;
+
+const ARCHIVE_MAGIC: &[u8] = b"!<arch>\n";
+const MEMBER_HEADER_SIZE: usize = 60;
+
+/// Signature of a short import object, as written to import libraries by MSVC and lld.
+const SHORT_IMPORT_SIGNATURE: &[u8] = &[0x00, 0x00, 0xff, 0xff];
+
+/// Section names of the long import objects written to import libraries by MinGW.
+const IMPORT_SECTION: &[u8] = b".idata$";
+
+/// Members of an ar archive, without the symbol and long name tables.
+fn archive_members(data: &[u8]) -> Vec<&[u8]> {
+    let mut members = Vec::new();
+
+    if !data.starts_with(ARCHIVE_MAGIC) {
+        return members;
+    }
+
+    let mut offset = ARCHIVE_MAGIC.len();
+
+    while offset + MEMBER_HEADER_SIZE <= data.len() {
+        let header = &data[offset..offset + MEMBER_HEADER_SIZE];
+
+        let name = String::from_utf8_lossy(&header[..16]);
+        let size = String::from_utf8_lossy(&header[48..58]).trim().parse::<usize>();
+
+        let Ok(size) = size else {
+            break;
+        };
+
+        let start = offset + MEMBER_HEADER_SIZE;
+        let end = start.saturating_add(size).min(data.len());
+
+        let name = name.trim_end();
+        if name != "/" && name != "//" && name != "/SYM64/" && !name.starts_with("__.SYMDEF") {
+            members.push(&data[start..end]);
+        }
+
+        // Members are aligned to two bytes.
+        offset = end + (end & 1);
+    }
+
+    members
+}
+
+fn is_import_member(member: &[u8]) -> bool {
+    member.starts_with(SHORT_IMPORT_SIGNATURE)
+        || member.windows(IMPORT_SECTION.len()).any(|x| x == IMPORT_SECTION)
+}
+
+/// Whether an archive is an import library for a DLL rather than a static library.
+///
+/// Both use the `.lib` extension with MSVC, so they can only be told apart by their contents.
+pub (crate) fn is_import_library(path: &Path) -> Result<bool, Error> {
+    let data = fs::read(path)?;
+
+    Ok(archive_members(data.as_slice()).iter().any(|x| is_import_member(x)))
+}
+
+/// Read the file name of the DLL an import library refers to, such as `foo-3.dll`.
+pub (crate) fn read_dll_name(path: &Path) -> Result<Option<String>, Error> {
+    let data = fs::read(path)?;
+
+    for member in archive_members(data.as_slice()).iter().filter(|x| is_import_member(x)) {
+        // The DLL name is stored as a null terminated string, after the symbol name for short
+        // import objects and in the `.idata$7` section for long import objects.
+        let name = member.split(|x| *x == 0)
+            .filter_map(|x| std::str::from_utf8(x).ok())
+            .find(|x| x.to_lowercase().ends_with(".dll") && x.chars().all(|c| c.is_ascii_graphic()));
+
+        if let Some(name) = name {
+            return Ok(Some(name.to_string()));
+        }
+    }
+
+    Ok(None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_directory::TestDirectory;
+
+    fn archive(members: &[(&str, &[u8])]) -> Vec<u8> {
+        let mut data = ARCHIVE_MAGIC.to_vec();
+
+        for (name, contents) in members.iter() {
+            let header = format!("{:<16}{:<12}{:<6}{:<6}{:<8}{:<10}`\n", name, 0, 0, 0, 644, contents.len());
+            assert_eq!(header.len(), MEMBER_HEADER_SIZE);
+
+            data.extend(header.bytes());
+            data.extend_from_slice(contents);
+
+            if data.len() % 2 == 1 {
+                data.push(b'\n');
+            }
+        }
+
+        data
+    }
+
+    /// Short import object for `symbol` exported by `dll`.
+    fn short_import(symbol: &str, dll: &str) -> Vec<u8> {
+        let mut member = SHORT_IMPORT_SIGNATURE.to_vec();
+        member.extend([0u8; 16]);
+        member.extend(format!("{}\0{}\0", symbol, dll).bytes());
+        member
+    }
+
+    /// MinGW import object, a COFF object with the DLL name in its `.idata$7` section.
+    fn long_import(dll: &str) -> Vec<u8> {
+        let mut member = vec![0x64, 0x86];
+        member.extend([0u8; 18]);
+        member.extend(b".idata$7\0\0\0\0");
+        member.extend(format!("{}\0", dll).bytes());
+        member
+    }
+
+    fn inspect(name: &str, data: &[u8]) -> (bool, Option<String>) {
+        let directory = TestDirectory::new(format!("import-library-{}", name).as_str());
+        let path = directory.write("foo.lib", data);
+
+        (is_import_library(path.as_path()).unwrap(), read_dll_name(path.as_path()).unwrap())
+    }
+
+    #[test]
+    fn reads_short_import_libraries() {
+        let data = archive(&[
+            ("/", b"\0\0\0\x01symbol table"),
+            ("//", b"long names/\n"),
+            ("foo-3.dll/", short_import("foo_init", "foo-3.dll").as_slice()),
+        ]);
+
+        assert_eq!(inspect("short", data.as_slice()), (true, Some("foo-3.dll".to_string())));
+    }
+
+    #[test]
+    fn reads_long_import_libraries() {
+        let data = archive(&[
+            ("/", b"\0\0\0\x01symbol table"),
+            ("d000000.o/", b"\x64\x86odd"),
+            ("d000001.o/", long_import("libfoo-3.DLL").as_slice()),
+        ]);
+
+        assert_eq!(inspect("long", data.as_slice()), (true, Some("libfoo-3.DLL".to_string())));
+    }
+
+    #[test]
+    fn ignores_static_libraries() {
+        let data = archive(&[
+            ("/", b"\0\0\0\x01foo_init"),
+            ("foo.o/", b"\x64\x86\0\0.text\0\0\0uses.dll\0"),
+        ]);
+
+        assert_eq!(inspect("static", data.as_slice()), (false, None));
+        assert_eq!(inspect("empty", ARCHIVE_MAGIC), (false, None));
+        assert_eq!(inspect("invalid", b"not an archive"), (false, None));
+    }
+
+    #[test]
+    fn reads_truncated_archives() {
+        let data = archive(&[("foo.o/", short_import("foo_init", "foo.dll").as_slice())]);
+        let members = archive_members(&data[..data.len() - 4]);
+
+        assert_eq!(members.len(), 1);
+        assert!(is_import_member(members[0]));
+        assert!(archive_members(&data[..ARCHIVE_MAGIC.len() + 10]).is_empty());
+    }
+}
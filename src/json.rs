@@ -0,0 +1,261 @@
+use std::iter::Peekable;
+use std::str::Chars;
+
+/// Minimal JSON document, enough to read CMake File API replies.
+#[derive(Debug, PartialEq)]
+pub (crate) enum Json {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<Json>),
+    Object(Vec<(String, Json)>),
+}
+
+impl Json {
+
+    pub (crate) fn parse(contents: &str) -> Result<Json, String> {
+        let mut chars = contents.chars().peekable();
+        let value = parse_value(&mut chars)?;
+
+        skip_whitespace(&mut chars);
+
+        match chars.next() {
+            None => Ok(value),
+            Some(c) => Err(format!("unexpected trailing character '{}'", c)),
+        }
+    }
+
+    pub (crate) fn get(&self, key: &str) -> Option<&Json> {
+        match self {
+            Json::Object(members) => members.iter().find(|(x, _)| x == key).map(|(_, x)| x),
+            _ => None,
+        }
+    }
+
+    pub (crate) fn as_str(&self) -> Option<&str> {
+        match self {
+            Json::String(value) => Some(value.as_str()),
+            _ => None,
+        }
+    }
+
+    /// Elements of an array, an empty slice for anything else.
+    pub (crate) fn elements(&self) -> &[Json] {
+        match self {
+            Json::Array(elements) => elements.as_slice(),
+            _ => &[],
+        }
+    }
+}
+
+fn skip_whitespace(chars: &mut Peekable<Chars>) {
+    while chars.peek().map(|x| x.is_whitespace()).unwrap_or(false) {
+        chars.next();
+    }
+}
+
+fn expect(
+    chars: &mut Peekable<Chars>,
+    literal: &str,
+) -> Result<(), String> {
+    for expected in literal.chars() {
+        if chars.next() != Some(expected) {
+            return Err(format!("expected '{}'", literal));
+        }
+    }
+
+    Ok(())
+}
+
+fn parse_value(chars: &mut Peekable<Chars>) -> Result<Json, String> {
+    skip_whitespace(chars);
+
+    match chars.peek() {
+        Some('n') => expect(chars, "null").map(|_| Json::Null),
+        Some('t') => expect(chars, "true").map(|_| Json::Bool(true)),
+        Some('f') => expect(chars, "false").map(|_| Json::Bool(false)),
+        Some('"') => parse_string(chars).map(Json::String),
+        Some('[') => {
+            chars.next();
+
+            let mut elements = Vec::new();
+            skip_whitespace(chars);
+
+            if chars.peek() == Some(&']') {
+                chars.next();
+                return Ok(Json::Array(elements));
+            }
+
+            loop {
+                elements.push(parse_value(chars)?);
+                skip_whitespace(chars);
+
+                match chars.next() {
+                    Some(',') => continue,
+                    Some(']') => return Ok(Json::Array(elements)),
+                    _ => return Err("expected ',' or ']'".to_string()),
+                }
+            }
+        },
+        Some('{') => {
+            chars.next();
+
+            let mut members = Vec::new();
+            skip_whitespace(chars);
+
+            if chars.peek() == Some(&'}') {
+                chars.next();
+                return Ok(Json::Object(members));
+            }
+
+            loop {
+                skip_whitespace(chars);
+
+                let key = parse_string(chars)?;
+                skip_whitespace(chars);
+                expect(chars, ":")?;
+
+                members.push((key, parse_value(chars)?));
+                skip_whitespace(chars);
+
+                match chars.next() {
+                    Some(',') => continue,
+                    Some('}') => return Ok(Json::Object(members)),
+                    _ => return Err("expected ',' or '}'".to_string()),
+                }
+            }
+        },
+        Some(c) if *c == '-' || c.is_ascii_digit() => {
+            let mut number = String::new();
+
+            while let Some(c) = chars.peek().filter(|x| x.is_ascii_digit() || "+-.eE".contains(**x)) {
+                number.push(*c);
+                chars.next();
+            }
+
+            number.parse::<f64>()
+                .map(Json::Number)
+                .map_err(|_| format!("invalid number '{}'", number))
+        },
+        Some(c) => Err(format!("unexpected character '{}'", c)),
+        None => Err("unexpected end of document".to_string()),
+    }
+}
+
+fn parse_string(chars: &mut Peekable<Chars>) -> Result<String, String> {
+    expect(chars, "\"")?;
+
+    let mut value = String::new();
+
+    loop {
+        match chars.next() {
+            Some('"') => return Ok(value),
+            Some('\\') => match chars.next() {
+                Some('b') => value.push('\u{8}'),
+                Some('f') => value.push('\u{c}'),
+                Some('n') => value.push('\n'),
+                Some('r') => value.push('\r'),
+                Some('t') => value.push('\t'),
+                Some('u') => {
+                    let mut code = parse_hex(chars)?;
+
+                    // Combine surrogate pairs.
+                    if (0xD800..0xDC00).contains(&code) {
+                        expect(chars, "\\u")?;
+                        let low = parse_hex(chars)?
+                            .checked_sub(0xDC00)
+                            .ok_or("invalid surrogate pair".to_string())?;
+
+                        code = 0x10000 + ((code - 0xD800) << 10) + low;
+                    }
+
+                    value.push(char::from_u32(code).unwrap_or(char::REPLACEMENT_CHARACTER));
+                },
+                Some(c) => value.push(c),
+                None => return Err("unterminated string".to_string()),
+            },
+            Some(c) => value.push(c),
+            None => return Err("unterminated string".to_string()),
+        }
+    }
+}
+
+fn parse_hex(chars: &mut Peekable<Chars>) -> Result<u32, String> {
+    let hex = chars.by_ref().take(4).collect::<String>();
+
+    u32::from_str_radix(hex.as_str(), 16)
+        .map_err(|_| format!("invalid unicode escape '{}'", hex))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn string(value: &str) -> Json {
+        Json::String(value.to_string())
+    }
+
+    #[test]
+    fn parses_escapes() {
+        assert_eq!(
+            Json::parse(r#""quote \" backslash \\ slash \/ controls \b\f\n\r\t""#).unwrap(),
+            string("quote \" backslash \\ slash / controls \u{8}\u{c}\n\r\t"),
+        );
+
+        assert_eq!(Json::parse(r#""\u00e9\u4E2D""#).unwrap(), string("\u{e9}\u{4e2d}"));
+        assert_eq!(Json::parse(r#""\ud83d\ude00""#).unwrap(), string("\u{1f600}"));
+        assert!(Json::parse(r#""unpaired \ud83d""#).is_err());
+        assert!(Json::parse(r#""\u12""#).is_err());
+        assert!(Json::parse(r#""unterminated"#).is_err());
+    }
+
+    #[test]
+    fn parses_numbers() {
+        assert_eq!(Json::parse("0").unwrap(), Json::Number(0.0));
+        assert_eq!(Json::parse("42").unwrap(), Json::Number(42.0));
+        assert_eq!(Json::parse("-1.5").unwrap(), Json::Number(-1.5));
+        assert_eq!(Json::parse("2.5e3").unwrap(), Json::Number(2500.0));
+        assert_eq!(Json::parse("1E-2").unwrap(), Json::Number(0.01));
+        assert!(Json::parse("-").is_err());
+        assert!(Json::parse("1.2.3").is_err());
+    }
+
+    #[test]
+    fn parses_nested_values() {
+        let json = Json::parse(r#"
+            {
+                "paths": { "build": "/build", "source": "/source" },
+                "targets": [ { "name": "foo", "id": 1 }, { "name": "bar", "id": 2 } ],
+                "empty": { "array": [], "object": {} },
+                "flags": [true, false, null]
+            }
+        "#).unwrap();
+
+        assert_eq!(json.get("paths").and_then(|x| x.get("build")).and_then(|x| x.as_str()), Some("/build"));
+
+        let names = json.get("targets").unwrap().elements().iter()
+            .filter_map(|x| x.get("name").and_then(|x| x.as_str()))
+            .collect::<Vec<&str>>();
+
+        assert_eq!(names, vec!["foo", "bar"]);
+
+        assert_eq!(json.get("empty").and_then(|x| x.get("array")), Some(&Json::Array(Vec::new())));
+        assert_eq!(json.get("empty").and_then(|x| x.get("object")), Some(&Json::Object(Vec::new())));
+        assert_eq!(json.get("flags").unwrap().elements(), &[Json::Bool(true), Json::Bool(false), Json::Null]);
+        assert!(json.get("missing").is_none());
+        assert!(json.get("flags").unwrap().get("name").is_none());
+        assert!(json.get("paths").unwrap().elements().is_empty());
+    }
+
+    #[test]
+    fn rejects_malformed_documents() {
+        assert!(Json::parse("").is_err());
+        assert!(Json::parse("[1, 2").is_err());
+        assert!(Json::parse("[1 2]").is_err());
+        assert!(Json::parse(r#"{"key" 1}"#).is_err());
+        assert!(Json::parse(r#"{"key": 1,}"#).is_err());
+        assert!(Json::parse("nul").is_err());
+        assert!(Json::parse("{} {}").is_err());
+    }
+}
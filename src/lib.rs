@@ -4,71 +4,259 @@
 //! that depend on both static and shared libraries.
 //!
 
-use std::fs;
+use std::{env, fs, io};
 use std::ops::Add;
-use std::path::Path;
-use crate::commands::{add_library_search_path, link_shared_library, link_static_library};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use crate::commands::{add_framework_search_path, add_library_search_path, add_link_group, add_rpath, link_framework, link_shared_library, link_static_library, print_warning, rerun_if_changed, set_metadata};
+use crate::error::unwrap_or_panic;
+use crate::resolve::{resolve_link_target, ArtifactKind, ResolvedTarget};
+use crate::types::link::{LinkModifiers, LinkTarget, RPath};
 use crate::types::local_library::LocalLibrary;
-use crate::variables::{platform, Platform, shared_library_extension, static_library_extension, target_directory};
+use crate::variables::{library_prefix, shared_library_extension, static_library_extension, target_directory, uses_gnu_linker, uses_msvc_naming};
+
+pub use crate::error::{CommandError, Error};
 
 pub mod types;
 
+pub (crate) mod error;
 pub (crate) mod variables;
 pub (crate) mod commands;
+pub (crate) mod cmake_package;
+pub (crate) mod download;
+pub (crate) mod file_api;
+pub (crate) mod git;
+pub (crate) mod glob;
+pub (crate) mod hash;
+pub (crate) mod import_library;
+pub (crate) mod json;
+pub (crate) mod process;
+pub (crate) mod resolve;
+pub (crate) mod soname;
+pub (crate) mod pkg_config;
+pub (crate) mod version;
+
+#[cfg(test)]
+pub (crate) mod test_directory;
 
 const LIBRARY_NAME_PREFIX: &str = "lib";
 
-fn get_static_library_name(library_name: &str) -> String {
-    // Omit the prefix for Windows since there is no convention for library names.
-    if platform() == Platform::Windows {
-        return library_name.to_string()
-            .add(static_library_extension());
-    }
+fn get_static_library_name(library_name: &str) -> Result<String, Error> {
+    Ok(library_prefix()?.to_string()
+        .add(library_name)
+        .add(static_library_extension()?))
+}
 
-    LIBRARY_NAME_PREFIX.to_string()
+fn get_shared_library_name(library_name: &str) -> Result<String, Error>  {
+    Ok(library_prefix()?.to_string()
         .add(library_name)
-        .add(static_library_extension())
+        .add(shared_library_extension()?))
 }
 
-fn get_shared_library_name(library_name: &str) -> String  {
-    // Omit the prefix for Windows since there is no convention for library names.
-    if platform() == Platform::Windows {
-        return library_name.to_string()
-            .add(shared_library_extension());
-    }
+/// File names of the import libraries that may be used to link a DLL on Windows, the naming
+/// convention of the target environment first.
+fn get_import_library_names(library_name: &str) -> Result<Vec<String>, Error> {
+    let msvc_name = library_name.to_string().add(".lib");
+    let gnu_names = [
+        LIBRARY_NAME_PREFIX.to_string().add(library_name).add(".dll.a"),
+        library_name.to_string().add(".dll.a"),
+    ];
 
-    LIBRARY_NAME_PREFIX.to_string()
-        .add(library_name)
-        .add(shared_library_extension())
+    Ok(match uses_msvc_naming()? {
+        true => [vec![msvc_name], gnu_names.to_vec()].concat(),
+        false => [gnu_names.to_vec(), vec![msvc_name]].concat(),
+    })
 }
 
+/// Derive the name passed to the linker from the file name of a library.
+fn get_library_name(library_path: &Path) -> String {
+    let file_name = library_path.file_name().unwrap_or_default().to_string_lossy();
+
+    let name = [".dll.a", ".lib", ".a", ".dll", ".dylib", ".so", ".tbd"]
+        .iter()
+        .find_map(|x| file_name.strip_suffix(x))
+        .or_else(|| file_name.find(".so.").map(|x| &file_name[..x]))
+        .unwrap_or(&file_name);
+
+    // MSVC libraries are linked by their file name, everything else drops the `lib` prefix.
+    match file_name.ends_with(".lib") {
+        true => name.to_string(),
+        false => name.strip_prefix(LIBRARY_NAME_PREFIX).unwrap_or(name).to_string(),
+    }
+}
+
+/// Copy a shared object to the target directory so it can be found at runtime.
+///
+/// Installs usually ship `libfoo.so -> libfoo.so.1 -> libfoo.so.1.2.3`, while the loader looks for
+/// the name given by the SONAME of the library. The real file is copied under its own name, and the
+/// symlinks leading to it as well as its SONAME are recreated next to it.
 fn copy_shared_object(
     target_directory: &Path,
     library_path: &Path,
-) {
+) -> Result<(), Error> {
     if !target_directory.exists() {
-        fs::create_dir_all(target_directory)
-            .expect("Could not create target directory.");
+        fs::create_dir_all(target_directory)?;
     }
 
     if !library_path.exists() {
-        panic!("Could not find shared object: {:?}", library_path);
+        return Err(Error::LibraryNotFound(
+            format!("could not find shared object: {:?}", library_path)
+        ));
+    }
+
+    let real_path = fs::canonicalize(library_path)?;
+    let real_name = real_path.file_name().unwrap().to_os_string();
+
+    let copy_path = target_directory.join(real_name.as_os_str());
+    remove_existing_file(copy_path.as_path())?;
+    fs::copy(real_path.as_path(), copy_path)?;
+
+    // Links are created innermost first, so that every link points to a file that exists.
+    let mut links = Vec::new();
+
+    if let Some(soname) = soname::read_soname(real_path.as_path())? {
+        if let Some(soname) = Path::new(soname.as_str()).file_name() {
+            links.push((soname.to_os_string(), real_name.clone()));
+        }
+    }
+
+    let mut chain = Vec::new();
+    let mut link_path = library_path.to_path_buf();
+
+    while let Ok(destination) = fs::read_link(link_path.as_path()) {
+        let destination = link_path.parent().unwrap_or(Path::new("")).join(destination);
+
+        let (Some(name), Some(destination_name)) = (link_path.file_name(), destination.file_name()) else {
+            break;
+        };
+
+        chain.push((name.to_os_string(), destination_name.to_os_string()));
+        link_path = destination;
+    }
+
+    links.extend(chain.into_iter().rev());
+
+    for (name, destination) in links {
+        if name == real_name || name == destination {
+            continue;
+        }
+
+        let link_path = target_directory.join(name.as_os_str());
+        remove_existing_file(link_path.as_path())?;
+
+        #[cfg(unix)]
+        std::os::unix::fs::symlink(destination.as_os_str(), link_path)?;
+
+        #[cfg(not(unix))]
+        fs::copy(target_directory.join(destination.as_os_str()), link_path)?;
+    }
+
+    Ok(())
+}
+
+/// Remove a file or symlink left over from a previous build, so that copying does not write
+/// through a stale symlink.
+fn remove_existing_file(path: &Path) -> Result<(), Error> {
+    if path.symlink_metadata().is_ok() {
+        fs::remove_file(path)?;
+    }
+
+    Ok(())
+}
+
+/// Join paths with the separator used by `PATH`, so they can be passed in a single variable.
+fn join_paths(paths: &[PathBuf]) -> Result<String, Error> {
+    env::join_paths(paths)
+        .map(|x| x.to_string_lossy().to_string())
+        .map_err(|e| Error::Io(io::Error::new(io::ErrorKind::InvalidInput, e)))
+}
+
+/// Directories of every library bound by the build script so far.
+struct BoundDirectories {
+    roots: Vec<PathBuf>,
+    include_directories: Vec<PathBuf>,
+    library_directories: Vec<PathBuf>,
+}
+
+static BOUND_DIRECTORIES: Mutex<BoundDirectories> = Mutex::new(BoundDirectories {
+    roots: Vec::new(),
+    include_directories: Vec::new(),
+    library_directories: Vec::new(),
+});
+
+/// Pass the directories on to dependent crates through `DEP_<links>_*` variables, see
+/// `LocalLibrary::from_dependency`.
+///
+/// Cargo only keeps the last value of each key, so the directories of every library bound so far
+/// are emitted again each time.
+fn emit_metadata(
+    root: &Path,
+    include_directories: &[PathBuf],
+    library_directories: &[PathBuf],
+) -> Result<(), Error> {
+    fn extend(directories: &mut Vec<PathBuf>, new_directories: &[PathBuf]) {
+        for directory in new_directories.iter() {
+            if !directories.contains(directory) {
+                directories.push(directory.clone());
+            }
+        }
+    }
+
+    let mut bound = BOUND_DIRECTORIES.lock().unwrap_or_else(|x| x.into_inner());
+
+    extend(&mut bound.roots, &[root.to_path_buf()]);
+    extend(&mut bound.include_directories, include_directories);
+    extend(&mut bound.library_directories, library_directories);
+
+    set_metadata("root", join_paths(bound.roots.as_slice())?);
+    set_metadata("include", join_paths(bound.include_directories.as_slice())?);
+    set_metadata("lib", join_paths(bound.library_directories.as_slice())?);
+
+    Ok(())
+}
+
+/// Add a runtime search path to the binaries, tests and examples of the current crate.
+///
+/// Binding a library already does this for the crate binding it. Crates that only depend on such
+/// a crate, such as the final executable, can call this from their own build script. Only
+/// `RPath::Origin` and `RPath::OriginLib` apply here since there is no library directory.
+///
+/// Panics if the target is not known, see `try_emit_rpath`.
+pub fn emit_rpath(rpath: RPath) {
+    unwrap_or_panic(try_emit_rpath(rpath))
+}
+
+/// Add a runtime search path to the binaries, tests and examples of the current crate, returning
+/// an error if the target is not known.
+pub fn try_emit_rpath(rpath: RPath) -> Result<(), Error> {
+    if rpath == RPath::LibraryDirectory {
+        return Ok(());
+    }
+
+    if let Some(rpath) = rpath.get_entry(Path::new(""))? {
+        add_rpath(rpath.as_str());
     }
 
-    fs::copy(
-        library_path,
-        target_directory.join(library_path.file_name().unwrap())
-    ).expect("Could not copy shared object.");
+    Ok(())
 }
 
 /// Trait for integrating a `LocalLibrary` into `cc::Build`.
 pub trait BindBuild {
 
     /// Binds a `LocalLibrary` to the `cc::Build` instance.
+    ///
+    /// Panics if the library could not be bound, see `try_bind_library`.
     fn bind_library(
         &mut self,
         library: LocalLibrary
     ) -> &mut cc::Build;
+
+    /// Binds a `LocalLibrary` to the `cc::Build` instance, returning an error if the library
+    /// could not be bound.
+    fn try_bind_library(
+        &mut self,
+        library: LocalLibrary
+    ) -> Result<&mut cc::Build, Error>;
 }
 
 impl BindBuild for cc::Build {
@@ -77,69 +265,205 @@ impl BindBuild for cc::Build {
         &mut self,
         library: LocalLibrary
     ) -> &mut cc::Build {
+        unwrap_or_panic(self.try_bind_library(library))
+    }
+
+    fn try_bind_library(
+        &mut self,
+        library: LocalLibrary
+    ) -> Result<&mut cc::Build, Error> {
+
+        let link_order = library.get_link_order();
+
+        // Static libraries that are part of a cycle are linked again inside a linker group,
+        // otherwise the linker may drop objects that are only referenced from later in the
+        // cycle. Linkers without groups get whole archives instead.
+        let mut cycle_targets = Vec::new();
+
+        for cycle in link_order.cycles.iter() {
+            let description = cycle.iter()
+                .map(|x| x.describe())
+                .collect::<Vec<String>>()
+                .join(" -> ");
+
+            print_warning(format!("Dependency cycle between libraries: {}", description));
+
+            for library in cycle.iter() {
+                cycle_targets.extend(library.get_link_targets().iter().map(|x| x.name.clone()));
+            }
+        }
 
         // Remove duplicates and invalid entries
-        let mut include_directories = library
-            .get_include_directories()
-            .clone();
+        let mut include_directories = Vec::new();
+        let mut library_directories = Vec::new();
+        let mut resolved_targets: Vec<ResolvedTarget> = Vec::new();
+        let mut system_link_targets = Vec::new();
+        let mut rpaths = Vec::new();
 
-        include_directories.dedup();
-        include_directories.retain(|x| x.is_dir());
+        for library in link_order.libraries.iter() {
+            for include_directory in library.get_include_directories() {
+                if include_directory.is_dir() && !include_directories.contains(include_directory) {
+                    if library.is_prebuilt() && !pkg_config::is_system_include_directory(include_directory) {
+                        rerun_if_changed(include_directory.as_path())?;
+                    }
 
-        self.includes(include_directories);
+                    include_directories.push(include_directory.clone());
+                }
+            }
 
-        let mut library_directories = library
-            .get_library_directories()
-            .clone();
+            let mut search_directories = library.get_library_directories().clone();
+            search_directories.retain(|x| x.is_dir());
 
-        library_directories.dedup();
-        library_directories.retain(|x| x.is_dir());
+            for library_directory in search_directories.iter() {
+                if !library_directories.contains(library_directory) {
+                    library_directories.push(library_directory.clone());
+                }
+            }
 
-        for library_directory in library_directories.iter() {
-            add_library_search_path(library_directory.as_path())
+            for link_target in library.get_link_targets() {
+                if resolved_targets.iter().any(|x| x.name == link_target.name) {
+                    continue;
+                }
+
+                let link_target = LinkTarget {
+                    modifiers: link_target.modifiers.or(library.get_link_modifiers()),
+                    ..link_target.clone()
+                };
+
+                let mut runtime_directories = library.get_runtime_directories().clone();
+                runtime_directories.retain(|x| x.is_dir());
+
+                match resolve_link_target(&link_target, search_directories.as_slice(), runtime_directories.as_slice()) {
+                    Ok(resolved_target) => {
+                        if library.is_prebuilt() {
+                            if let Some(path) = &resolved_target.path {
+                                rerun_if_changed(path.as_path())?;
+                            }
+                        }
+
+                        if let Some(runtime_path) = &resolved_target.runtime_path {
+                            if let Some(rpath) = library.get_rpath().get_entry(runtime_path)? {
+                                if !rpaths.contains(&rpath) {
+                                    rpaths.push(rpath);
+                                }
+                            }
+                        }
+
+                        resolved_targets.push(resolved_target);
+                    },
+                    Err(e) if !library.is_strict() => print_warning(e),
+                    Err(e) => return Err(e),
+                }
+            }
+
+            for system_link_target in library.get_system_link_targets() {
+                if !system_link_targets.contains(system_link_target) {
+                    system_link_targets.push(system_link_target.clone());
+                }
+            }
         }
 
-        let mut link_targets = library
-            .get_link_targets()
-            .clone();
+        emit_metadata(library.get_install_directory(), include_directories.as_slice(), library_directories.as_slice())?;
 
-        link_targets.dedup();
+        self.includes(include_directories);
+
+        for library_directory in library_directories.iter() {
+            add_library_search_path(library_directory.as_path())?;
+        }
 
-        let target_directory = target_directory();
+        let target_directory = target_directory()?;
+        let link_groups = uses_gnu_linker()?;
+        let mut grouped_archives = Vec::new();
 
-        // Always prefer static libraries over shared libraries
-        for library in link_targets.iter() {
-            for library_directory in library_directories.iter() {
-                let static_library_path = library_directory
-                    .join(get_static_library_name(library));
+        for resolved_target in resolved_targets.iter() {
+            match &resolved_target.path {
+                Some(path) => println!("Linking {} from {:?}", resolved_target.name, path),
+                None => println!("Linking {} from the system", resolved_target.name),
+            }
 
-                let shared_library_path = library_directory
-                    .join(get_shared_library_name(library));
+            let mut modifiers = resolved_target.modifiers;
 
-                if static_library_path.exists() {
-                    link_static_library(library);
-                } else if shared_library_path.exists() {
+            match resolved_target.kind {
+                ArtifactKind::Static => {
+                    if cycle_targets.contains(&resolved_target.name) {
+                        match (link_groups, &resolved_target.path) {
+                            (true, Some(path)) => grouped_archives.push(path.as_path()),
+                            _ => { modifiers.whole_archive(true); },
+                        }
+                    }
+                    link_static_library(&resolved_target.name, &modifiers);
+                },
+                ArtifactKind::Shared => {
                     // Copy shared object to target directory
-                    copy_shared_object(
-                        target_directory.as_path(),
-                        shared_library_path.as_path()
-                    );
-                    link_shared_library(library);
-                }
+                    match &resolved_target.runtime_path {
+                        Some(path) => copy_shared_object(
+                            target_directory.as_path(),
+                            path.as_path()
+                        )?,
+                        None => print_warning(format!(
+                            "Could not find the runtime library of {}, it must be on the library search path when running",
+                            resolved_target.name
+                        )),
+                    }
+                    link_shared_library(&resolved_target.name, &modifiers);
+                },
+                ArtifactKind::Framework => {
+                    if let Some(directory) = resolved_target.path.as_ref().and_then(|x| x.parent()) {
+                        add_framework_search_path(directory)?;
+                    }
+                    link_framework(&resolved_target.name, &modifiers);
+                },
             }
         }
 
-        // Link against any system libraries.
-        let mut system_link_targets = library
-            .get_system_link_targets()
-            .clone();
+        if !grouped_archives.is_empty() {
+            add_link_group(grouped_archives.as_slice())?;
+        }
 
-        system_link_targets.dedup();
+        for rpath in rpaths.iter() {
+            add_rpath(rpath);
+        }
 
+        // Link against any system libraries.
         for library in system_link_targets.iter() {
-            link_shared_library(library);
+            link_shared_library(library, &LinkModifiers::default());
         }
 
-        self
+        Ok(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn derives_library_names() {
+        for (file_name, name) in [
+            ("libfoo.a", "foo"),
+            ("libfoo.so", "foo"),
+            ("libfoo.so.1.2.3", "foo"),
+            ("libfoo.dylib", "foo"),
+            ("libfoo.tbd", "foo"),
+            ("libfoo.dll.a", "foo"),
+            ("foo.dll.a", "foo"),
+            ("foo-3.dll", "foo-3"),
+            ("foo.lib", "foo"),
+            ("libfoo.lib", "libfoo"),
+            ("foo", "foo"),
+        ] {
+            assert_eq!(get_library_name(Path::new("/usr/lib").join(file_name).as_path()), name);
+        }
+    }
+
+    #[test]
+    fn joins_metadata_paths() {
+        let paths = vec![PathBuf::from("/opt/foo/include"), PathBuf::from("/opt/bar/include")];
+        let joined = join_paths(paths.as_slice()).unwrap();
+
+        assert_eq!(env::split_paths(joined.as_str()).collect::<Vec<PathBuf>>(), paths);
+
+        #[cfg(unix)]
+        assert!(join_paths(&[PathBuf::from("/opt/a:b")]).is_err());
     }
 }
@@ -0,0 +1,758 @@
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use crate::error::Error;
+use crate::commands::rerun_if_changed;
+use crate::variables::{host, platform, target, target_env_var, Platform};
+use crate::version::VersionReq;
+use crate::{get_import_library_names, get_shared_library_name, get_static_library_name};
+
+const DEFAULT_SEARCH_DIRECTORIES: [&str; 8] = [
+    "/usr/local/lib/{multiarch}/pkgconfig",
+    "/usr/local/lib/pkgconfig",
+    "/usr/local/share/pkgconfig",
+    "/usr/lib/{multiarch}/pkgconfig",
+    "/usr/lib64/pkgconfig",
+    "/usr/lib/pkgconfig",
+    "/usr/share/pkgconfig",
+    "/opt/homebrew/lib/pkgconfig",
+];
+
+const SYSTEM_INCLUDE_DIRECTORIES: [&str; 2] = [
+    "/usr/include",
+    "/usr/local/include",
+];
+
+const SYSTEM_LIBRARY_DIRECTORIES: [&str; 7] = [
+    "/lib",
+    "/lib64",
+    "/lib/{multiarch}",
+    "/usr/lib",
+    "/usr/lib32",
+    "/usr/lib64",
+    "/usr/lib/{multiarch}",
+];
+
+/// Debian style multiarch tuple for the target, i.e. `x86_64-linux-gnu`.
+fn multiarch() -> Result<String, Error> {
+    let target = target()?;
+    let parts = target.split('-').collect::<Vec<&str>>();
+
+    let arch = match parts[0] {
+        "i586" | "i686" => "i386",
+        x if x.starts_with("arm") => "arm",
+        x => x,
+    };
+
+    Ok(match parts.len() {
+        4 => format!("{}-{}-{}", arch, parts[2], parts[3]),
+        3 => format!("{}-{}", arch, parts[2]),
+        _ => target.clone(),
+    })
+}
+
+fn expand_directories(directories: &[&str]) -> Result<Vec<PathBuf>, Error> {
+    let multiarch = multiarch()?;
+
+    Ok(directories.iter()
+        .map(|x| PathBuf::from(x.replace("{multiarch}", multiarch.as_str())))
+        .collect())
+}
+
+fn split_paths(value: &str) -> Vec<PathBuf> {
+    env::split_paths(value)
+        .filter(|x| !x.as_os_str().is_empty())
+        .collect()
+}
+
+/// Split a flags field into arguments, honouring quotes and backslash escapes.
+fn split_arguments(value: &str) -> Vec<String> {
+    let mut arguments = Vec::new();
+    let mut current = String::new();
+    let mut in_argument = false;
+    let mut quote: Option<char> = None;
+    let mut chars = value.chars();
+
+    while let Some(c) = chars.next() {
+        match (c, quote) {
+            ('\\', q) if q != Some('\'') => {
+                if let Some(escaped) = chars.next() {
+                    current.push(escaped);
+                }
+                in_argument = true;
+            },
+            (c, Some(q)) if c == q => quote = None,
+            (c, Some(_)) => current.push(c),
+            ('"' | '\'', None) => {
+                quote = Some(c);
+                in_argument = true;
+            },
+            (c, None) if c.is_whitespace() => {
+                if in_argument {
+                    arguments.push(std::mem::take(&mut current));
+                    in_argument = false;
+                }
+            },
+            (c, None) => {
+                current.push(c);
+                in_argument = true;
+            },
+        }
+    }
+
+    if in_argument {
+        arguments.push(current);
+    }
+
+    arguments
+}
+
+/// Split a `Requires` field into package names and their version requirements.
+fn split_requires(value: &str) -> Result<Vec<(String, VersionReq)>, Error> {
+    let is_operator_char = |c: char| matches!(c, '<' | '>' | '=' | '!');
+
+    // Operators do not need to be separated from the name or version, `foo>=1.2` is valid.
+    let mut tokens = Vec::new();
+    let mut current = String::new();
+
+    for c in value.chars() {
+        let separator = c == ',' || c.is_whitespace();
+
+        if !current.is_empty() && (separator || current.ends_with(is_operator_char) != is_operator_char(c)) {
+            tokens.push(std::mem::take(&mut current));
+        }
+
+        if !separator {
+            current.push(c);
+        }
+    }
+
+    if !current.is_empty() {
+        tokens.push(current);
+    }
+
+    let mut requires = Vec::new();
+
+    let mut i = 0;
+    while i < tokens.len() {
+        let name = tokens[i].clone();
+        i += 1;
+
+        if i < tokens.len() && tokens[i].starts_with(is_operator_char) {
+            let is_operator = matches!(tokens[i].as_str(), "=" | "==" | "!=" | "<" | "<=" | ">" | ">=");
+
+            if !is_operator || i + 1 == tokens.len() {
+                return Err(Error::InvalidVersion(value.to_string()));
+            }
+
+            let requirement = format!("{} {}", tokens[i], tokens[i + 1]);
+            requires.push((name, VersionReq::parse(requirement.as_str())?));
+            i += 2;
+        } else {
+            requires.push((name, VersionReq::any()));
+        }
+    }
+
+    Ok(requires)
+}
+
+/// A parsed `.pc` file.
+struct PcFile {
+    variables: HashMap<String, String>,
+    fields: HashMap<String, String>,
+}
+
+impl PcFile {
+
+    fn parse(
+        path: &Path,
+        sysroot: &Option<PathBuf>,
+    ) -> Result<PcFile, Error> {
+        let contents = fs::read_to_string(path)?;
+
+        Ok(PcFile::parse_contents(contents.as_str(), path.parent().unwrap_or(Path::new("")), sysroot))
+    }
+
+    fn parse_contents(
+        contents: &str,
+        directory: &Path,
+        sysroot: &Option<PathBuf>,
+    ) -> PcFile {
+        let mut pc_file = PcFile {
+            variables: HashMap::new(),
+            fields: HashMap::new(),
+        };
+
+        pc_file.variables.insert(
+            "pcfiledir".to_string(),
+            directory.to_string_lossy().to_string()
+        );
+
+        pc_file.variables.insert(
+            "pc_sysrootdir".to_string(),
+            sysroot.as_ref().map(|x| x.to_string_lossy().to_string()).unwrap_or("/".to_string())
+        );
+
+        let mut logical_line = String::new();
+
+        for line in contents.lines() {
+            // Join continuation lines before parsing.
+            if let Some(line) = line.strip_suffix('\\') {
+                logical_line.push_str(line);
+                continue;
+            }
+
+            logical_line.push_str(line);
+            let line = std::mem::take(&mut logical_line);
+
+            let line = match line.find('#') {
+                Some(index) => &line[..index],
+                None => line.as_str(),
+            };
+
+            let separator = line.find([':', '=']);
+
+            let Some(separator) = separator else {
+                continue;
+            };
+
+            let key = line[..separator].trim().to_string();
+            let value = pc_file.expand(line[separator + 1..].trim());
+
+            if key.is_empty() {
+                continue;
+            }
+
+            match &line[separator..separator + 1] {
+                "=" => pc_file.variables.insert(key, value),
+                _ => pc_file.fields.insert(key.to_lowercase(), value),
+            };
+        }
+
+        pc_file
+    }
+
+    /// Expand `${variable}` references using the variables defined so far.
+    fn expand(&self, value: &str) -> String {
+        let mut expanded = String::new();
+        let mut rest = value;
+
+        while let Some(index) = rest.find('$') {
+            expanded.push_str(&rest[..index]);
+            rest = &rest[index..];
+
+            if let Some(stripped) = rest.strip_prefix("$$") {
+                expanded.push('$');
+                rest = stripped;
+            } else if let (true, Some(end)) = (rest.starts_with("${"), rest.find('}')) {
+                let name = &rest[2..end];
+                expanded.push_str(self.variables.get(name).map(|x| x.as_str()).unwrap_or(""));
+                rest = &rest[end + 1..];
+            } else {
+                expanded.push('$');
+                rest = &rest[1..];
+            }
+        }
+
+        expanded.push_str(rest);
+        expanded
+    }
+
+    fn field(&self, name: &str) -> &str {
+        self.fields.get(name).map(|x| x.as_str()).unwrap_or("")
+    }
+}
+
+/// Flags collected from a package and all of its requirements.
+pub (crate) struct PkgConfigPackage {
+    pub (crate) version: String,
+    pub (crate) prefix: PathBuf,
+
+    pub (crate) include_directories: Vec<PathBuf>,
+    pub (crate) library_directories: Vec<PathBuf>,
+    pub (crate) libraries: Vec<String>,
+    pub (crate) frameworks: Vec<String>,
+}
+
+struct Probe {
+    search_directories: Vec<PathBuf>,
+    sysroot: Option<PathBuf>,
+
+    system_include_directories: Vec<PathBuf>,
+    system_library_directories: Vec<PathBuf>,
+
+    link_static: bool,
+    cflags_visited: Vec<String>,
+    libs_in_progress: Vec<String>,
+    package: PkgConfigPackage,
+}
+
+impl Probe {
+
+    fn new() -> Result<Probe, Error> {
+        let sysroot = target_env_var("PKG_CONFIG_SYSROOT_DIR")?
+            .filter(|x| !x.is_empty())
+            .map(PathBuf::from);
+
+        let mut search_directories = target_env_var("PKG_CONFIG_PATH")?
+            .map(|x| split_paths(x.as_str()))
+            .unwrap_or_default();
+
+        // PKG_CONFIG_LIBDIR replaces the default search path. When cross compiling the defaults
+        // belong to the host so they are only used relative to a sysroot.
+        match target_env_var("PKG_CONFIG_LIBDIR")? {
+            Some(libdir) => search_directories.extend(split_paths(libdir.as_str())),
+            None if host()? == target()? => {
+                search_directories.extend(expand_directories(&DEFAULT_SEARCH_DIRECTORIES)?)
+            },
+            None => match &sysroot {
+                Some(sysroot) => search_directories.extend(
+                    expand_directories(&DEFAULT_SEARCH_DIRECTORIES)?
+                        .iter()
+                        .map(|x| sysroot.join(x.strip_prefix("/").unwrap_or(x)))
+                ),
+                None => return Err(Error::LibraryNotFound(
+                    "cross compiling with pkg-config requires PKG_CONFIG_LIBDIR or \
+                    PKG_CONFIG_SYSROOT_DIR to be set".to_string()
+                )),
+            },
+        }
+
+        let system_include_directories = match target_env_var("PKG_CONFIG_ALLOW_SYSTEM_CFLAGS")? {
+            Some(_) => Vec::new(),
+            None => expand_directories(&SYSTEM_INCLUDE_DIRECTORIES)?,
+        };
+
+        let system_library_directories = match target_env_var("PKG_CONFIG_ALLOW_SYSTEM_LIBS")? {
+            Some(_) => Vec::new(),
+            None => expand_directories(&SYSTEM_LIBRARY_DIRECTORIES)?,
+        };
+
+        Ok(Probe {
+            search_directories,
+            sysroot,
+
+            system_include_directories,
+            system_library_directories,
+
+            link_static: target_env_var("PKG_CONFIG_ALL_STATIC")?.is_some(),
+            cflags_visited: Vec::new(),
+            libs_in_progress: Vec::new(),
+            package: PkgConfigPackage {
+                version: String::new(),
+                prefix: PathBuf::new(),
+
+                include_directories: Vec::new(),
+                library_directories: Vec::new(),
+                libraries: Vec::new(),
+                frameworks: Vec::new(),
+            },
+        })
+    }
+
+    fn load(
+        &self,
+        name: &str,
+    ) -> Result<PcFile, Error> {
+        let file_name = format!("{}.pc", name);
+
+        let path = self.search_directories.iter()
+            .map(|x| x.join(file_name.as_str()))
+            .find(|x| x.is_file())
+            .ok_or_else(|| Error::LibraryNotFound(
+                format!("could not find pkg-config file {} in {:?}", file_name, self.search_directories)
+            ))?;
+
+        rerun_if_changed(path.as_path())?;
+
+        PcFile::parse(path.as_path(), &self.sysroot)
+    }
+
+    /// Prepend the sysroot to an absolute path, unless it already points into the sysroot.
+    fn sysroot_path(
+        &self,
+        path: &str,
+    ) -> PathBuf {
+        match &self.sysroot {
+            Some(sysroot) if path.starts_with('/') && !Path::new(path).starts_with(sysroot) => {
+                sysroot.join(&path[1..])
+            },
+            _ => PathBuf::from(path),
+        }
+    }
+
+    fn apply_flags(
+        &mut self,
+        flags: &str,
+        libraries: bool,
+    ) {
+        let arguments = split_arguments(flags);
+        let mut arguments = arguments.iter();
+
+        while let Some(argument) = arguments.next() {
+            let mut value_of = |flag: &str| -> Option<String> {
+                match argument.strip_prefix(flag) {
+                    Some("") => arguments.next().cloned(),
+                    Some(value) => Some(value.to_string()),
+                    None => None,
+                }
+            };
+
+            if let Some(path) = value_of("-I").or_else(|| value_of("-isystem")) {
+                let path = self.sysroot_path(path.as_str());
+
+                if !self.system_include_directories.contains(&path)
+                    && !self.package.include_directories.contains(&path) {
+                    self.package.include_directories.push(path);
+                }
+            } else if !libraries {
+                continue;
+            } else if let Some(path) = value_of("-L") {
+                let path = self.sysroot_path(path.as_str());
+
+                if !self.system_library_directories.contains(&path)
+                    && !self.package.library_directories.contains(&path) {
+                    self.package.library_directories.push(path);
+                }
+            } else if let Some(library) = value_of("-l") {
+                push_last(&mut self.package.libraries, library);
+            } else if let Some(framework) = value_of("-framework") {
+                push_last(&mut self.package.frameworks, framework);
+            } else if argument == "-pthread" {
+                push_last(&mut self.package.libraries, "pthread".to_string());
+            }
+        }
+    }
+
+    /// Whether any of the libraries found so far resolve to a static archive, in which case
+    /// private dependencies are required as well.
+    fn has_static_library(&self) -> Result<bool, Error> {
+        for library in self.package.libraries.iter() {
+            let static_library_name = get_static_library_name(library)?;
+
+            if self.package.library_directories.iter().any(|x| x.join(&static_library_name).is_file()) {
+                return Ok(true);
+            }
+        }
+
+        Ok(false)
+    }
+
+    /// Collect the flags of a package and the packages it requires, the same as
+    /// `pkg-config --cflags --libs`.
+    ///
+    /// The compile flags of every requirement are collected, including private ones since their
+    /// headers may be included by the public headers. Libraries are only collected when `libs` is
+    /// set, private requirements only provide them when linking statically.
+    ///
+    /// Libraries are collected again each time a package is reached so that they are linked after
+    /// every package that requires them, only a requirement cycle stops the walk.
+    fn probe(
+        &mut self,
+        name: &str,
+        requirement: &VersionReq,
+        top_level: bool,
+        libs: bool,
+    ) -> Result<(), Error> {
+        let cflags_visited = self.cflags_visited.iter().any(|x| x == name);
+        let libs = libs && !self.libs_in_progress.iter().any(|x| x == name);
+
+        if cflags_visited && !libs {
+            return Ok(());
+        }
+
+        if !cflags_visited {
+            self.cflags_visited.push(name.to_string());
+        }
+
+        if libs {
+            self.libs_in_progress.push(name.to_string());
+        }
+
+        let pc_file = self.load(name)?;
+        let version = pc_file.field("version").to_string();
+
+        if !requirement.matches(version.as_str()) {
+            return Err(Error::VersionMismatch {
+                name: name.to_string(),
+                version,
+                required: requirement.as_str().to_string(),
+            });
+        }
+
+        if !cflags_visited {
+            self.apply_flags(pc_file.field("cflags"), false);
+        }
+
+        if libs {
+            self.apply_flags(pc_file.field("libs"), true);
+        }
+
+        if top_level {
+            self.package.version = version;
+            self.package.prefix = match pc_file.variables.get("prefix") {
+                Some(prefix) => self.sysroot_path(prefix.as_str()),
+                None => PathBuf::from(pc_file.variables.get("pcfiledir").cloned().unwrap_or_default()),
+            };
+
+            self.link_static = self.link_static || self.has_static_library()?;
+        }
+
+        if libs && self.link_static {
+            self.apply_flags(pc_file.field("libs.private"), true);
+        }
+
+        for (requires, requirement) in split_requires(pc_file.field("requires"))? {
+            self.probe(requires.as_str(), &requirement, false, libs)?;
+        }
+
+        let private_libs = libs && self.link_static;
+
+        for (requires, requirement) in split_requires(pc_file.field("requires.private"))? {
+            self.probe(requires.as_str(), &requirement, false, private_libs)?;
+        }
+
+        if libs {
+            self.libs_in_progress.pop();
+        }
+
+        Ok(())
+    }
+}
+
+/// Whether a directory belongs to the system headers, such as `/usr/include/glib-2.0`. These are
+/// updated by the system package manager and are not watched for changes.
+pub (crate) fn is_system_include_directory(path: &Path) -> bool {
+    SYSTEM_INCLUDE_DIRECTORIES.iter().any(|x| path.starts_with(x))
+}
+
+/// Add a flag, moving it to the end if it was already added. Like pkg-config the last
+/// occurrence is kept, which is the one that comes after everything that needs it.
+fn push_last(
+    values: &mut Vec<String>,
+    value: String,
+) {
+    values.retain(|x| x != &value);
+    values.push(value);
+}
+
+/// Find a package using pkg-config's search rules and collect its flags, including those of
+/// the packages it requires.
+pub (crate) fn probe(
+    name: &str,
+    requirement: &VersionReq,
+) -> Result<PkgConfigPackage, Error> {
+    let mut probe = Probe::new()?;
+    probe.probe(name, requirement, true, true)?;
+
+    Ok(probe.package)
+}
+
+/// Whether a library can be found in one of the directories collected for the package, as
+/// opposed to being provided by the system.
+pub (crate) fn is_local_library(
+    package: &PkgConfigPackage,
+    library: &str,
+) -> Result<bool, Error> {
+    let mut file_names = vec![get_static_library_name(library)?];
+
+    // DLLs are linked through import libraries, which are found next to static libraries.
+    match platform()? {
+        Platform::Windows => file_names.extend(get_import_library_names(library)?),
+        _ => file_names.push(get_shared_library_name(library)?),
+    }
+
+    Ok(package.library_directories.iter().any(|x| {
+        file_names.iter().any(|file_name| x.join(file_name).is_file())
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_directory::TestDirectory;
+
+    fn write_packages(
+        name: &str,
+        packages: &[(&str, &str)],
+    ) -> TestDirectory {
+        let directory = TestDirectory::new(format!("pkg-config-{}", name).as_str());
+
+        for (package, contents) in packages {
+            directory.write(format!("{}.pc", package).as_str(), contents);
+        }
+
+        directory
+    }
+
+    fn probe_packages(
+        directory: &Path,
+        name: &str,
+        link_static: bool,
+    ) -> Result<PkgConfigPackage, Error> {
+        let mut probe = Probe {
+            search_directories: vec![directory.to_path_buf()],
+            sysroot: None,
+
+            system_include_directories: Vec::new(),
+            system_library_directories: Vec::new(),
+
+            link_static,
+            cflags_visited: Vec::new(),
+            libs_in_progress: Vec::new(),
+            package: PkgConfigPackage {
+                version: String::new(),
+                prefix: PathBuf::new(),
+
+                include_directories: Vec::new(),
+                library_directories: Vec::new(),
+                libraries: Vec::new(),
+                frameworks: Vec::new(),
+            },
+        };
+
+        probe.probe(name, &VersionReq::any(), true, true).map(|_| probe.package)
+    }
+
+    /// `top` requires `a` and `b`, `a` privately requires `c` which `b` requires publicly, and `c`
+    /// privately requires `d`.
+    const GRAPH: [(&str, &str); 5] = [
+        ("top", "Version: 1.0\nRequires: a b\n"),
+        ("a", "Version: 1.0\nLibs: -la\nRequires.private: c >= 1\n"),
+        ("b", "Version: 1.0\nLibs: -lb\nRequires: c\n"),
+        ("c", "Version: 1.5\nCflags: -I/c\nLibs: -lc\nRequires.private: d\n"),
+        ("d", "Version: 1.0\nCflags: -I/d\nLibs: -ld\n"),
+    ];
+
+    #[test]
+    fn splits_arguments() {
+        assert_eq!(
+            split_arguments(r#"-I"/path with/spaces" -DNAME='a b'   -I/escaped\ space -L/lib"#),
+            vec!["-I/path with/spaces", "-DNAME=a b", "-I/escaped space", "-L/lib"],
+        );
+
+        assert_eq!(split_arguments(r#"-DEMPTY="" 'single \ quoted'"#), vec!["-DEMPTY=", r"single \ quoted"]);
+        assert!(split_arguments("  ").is_empty());
+    }
+
+    #[test]
+    fn parses_variables_and_fields() {
+        let pc_file = PcFile::parse_contents(
+            "# comment\n\
+            prefix=/opt/foo\n\
+            exec_prefix=${prefix}\n\
+            libdir=${exec_prefix}/lib # trailing comment\n\
+            price=$$5\n\
+            \n\
+            Name: foo\n\
+            VERSION: 1.2.3\n\
+            Cflags: -I${prefix}/include \\\n    -DFOO=${undefined}x\n\
+            Libs: -L${libdir} -lfoo\n\
+            local=${pcfiledir}/local\n",
+            Path::new("/pc/dir"),
+            &None,
+        );
+
+        assert_eq!(pc_file.variables["libdir"], "/opt/foo/lib");
+        assert_eq!(pc_file.variables["price"], "$5");
+        assert_eq!(pc_file.variables["local"], "/pc/dir/local");
+        assert_eq!(pc_file.variables["pc_sysrootdir"], "/");
+        assert_eq!(pc_file.field("version"), "1.2.3");
+        assert_eq!(pc_file.field("cflags"), "-I/opt/foo/include     -DFOO=x");
+        assert_eq!(pc_file.field("libs"), "-L/opt/foo/lib -lfoo");
+        assert_eq!(pc_file.field("requires"), "");
+    }
+
+    #[test]
+    fn parses_sysroot_variable() {
+        let pc_file = PcFile::parse_contents("root=${pc_sysrootdir}\n", Path::new("/"), &Some(PathBuf::from("/sysroot")));
+
+        assert_eq!(pc_file.variables["root"], "/sysroot");
+    }
+
+    #[test]
+    fn splits_requires() {
+        let requires = split_requires("foo >= 1.2, bar  baz = 3,qux<2 , quux != 1").unwrap();
+        let names = requires.iter().map(|(x, _)| x.as_str()).collect::<Vec<&str>>();
+
+        assert_eq!(names, vec!["foo", "bar", "baz", "qux", "quux"]);
+
+        assert!(requires[0].1.matches("1.2"));
+        assert!(!requires[0].1.matches("1.1"));
+        assert!(requires[1].1.matches("0.1"));
+        assert!(requires[2].1.matches("3"));
+        assert!(!requires[2].1.matches("3.1"));
+        assert!(requires[3].1.matches("1.9"));
+        assert!(!requires[3].1.matches("2"));
+        assert!(!requires[4].1.matches("1"));
+        assert!(requires[4].1.matches("1.1"));
+
+        assert_eq!(split_requires("foo>=1.2,bar").unwrap().len(), 2);
+        assert!(split_requires("foo >= 1, bar > 2 < 3").is_ok());
+        assert!(split_requires("foo => 1").is_err());
+        assert!(split_requires("foo >=").is_err());
+        assert!(split_requires("").unwrap().is_empty());
+    }
+
+    #[test]
+    fn collects_cflags_of_private_requirements() {
+        let directory = write_packages("dynamic", &GRAPH);
+        let package = probe_packages(directory.as_path(), "top", false).unwrap();
+
+        assert_eq!(package.version, "1.0");
+        assert_eq!(package.include_directories, vec![PathBuf::from("/c"), PathBuf::from("/d")]);
+
+        // `c` is first reached as a private requirement of `a`, its libraries are still needed
+        // by `b`. The libraries of the private requirement `d` are not.
+        assert_eq!(package.libraries, vec!["a", "b", "c"]);
+    }
+
+    #[test]
+    fn collects_libs_of_private_requirements_when_static() {
+        let directory = write_packages("static", &GRAPH);
+        let package = probe_packages(directory.as_path(), "top", true).unwrap();
+
+        assert_eq!(package.include_directories, vec![PathBuf::from("/c"), PathBuf::from("/d")]);
+        // `c` is reached again through `b`, its libraries and those of `d` move after `b`.
+        assert_eq!(package.libraries, vec!["a", "b", "c", "d"]);
+    }
+
+    #[test]
+    fn checks_versions_of_private_requirements() {
+        let mut packages = GRAPH;
+        packages[1] = ("a", "Version: 1.0\nRequires.private: c >= 2\n");
+
+        let directory = write_packages("version", &packages);
+
+        match probe_packages(directory.as_path(), "top", false) {
+            Err(Error::VersionMismatch { name, version, required }) => {
+                assert_eq!((name.as_str(), version.as_str(), required.as_str()), ("c", "1.5", ">= 2"));
+            },
+            _ => panic!("expected a version mismatch"),
+        }
+    }
+
+    #[test]
+    fn reports_missing_packages() {
+        let directory = write_packages("missing", &[("top", "Version: 1\nRequires.private: missing\n")]);
+
+        assert!(matches!(probe_packages(directory.as_path(), "top", false), Err(Error::LibraryNotFound(_))));
+    }
+
+    #[test]
+    fn detects_system_include_directories() {
+        assert!(is_system_include_directory(Path::new("/usr/include")));
+        assert!(is_system_include_directory(Path::new("/usr/include/glib-2.0")));
+        assert!(!is_system_include_directory(Path::new("/usr/includes")));
+        assert!(!is_system_include_directory(Path::new("/opt/foo/include")));
+    }
+
+    #[test]
+    fn collects_frameworks() {
+        let directory = write_packages("frameworks", &[("top", "Version: 1\nLibs: -framework CoreFoundation -framework Security\n")]);
+        let package = probe_packages(directory.as_path(), "top", false).unwrap();
+
+        assert_eq!(package.frameworks, vec!["CoreFoundation", "Security"]);
+    }
+}
@@ -0,0 +1,133 @@
+use std::{env, io, thread};
+use std::io::{Read, Write};
+use std::process::{Command, Stdio};
+use crate::error::CommandError;
+
+fn command_line(command: &Command) -> String {
+    let mut line = command.get_program().to_string_lossy().to_string();
+
+    for arg in command.get_args() {
+        line.push(' ');
+        line.push_str(&arg.to_string_lossy());
+    }
+
+    line
+}
+
+fn command_error(
+    command: &Command,
+    status: Option<i32>,
+    stderr: String,
+) -> CommandError {
+    let directory = command.get_current_dir()
+        .map(|x| x.to_path_buf())
+        .unwrap_or_else(|| env::current_dir().unwrap_or_default());
+
+    CommandError {
+        command: command_line(command),
+        directory,
+        status,
+        stderr,
+    }
+}
+
+/// Run a command to completion, capturing its standard output if `capture_stdout` is set.
+///
+/// Standard error is forwarded to our own standard error as it arrives, so that progress and hangs
+/// show up in the build log, and is kept so it can be reported on failure.
+fn execute(
+    command: &mut Command,
+    capture_stdout: bool,
+) -> Result<String, CommandError> {
+    let stdout = match capture_stdout {
+        true => Stdio::piped(),
+        false => Stdio::inherit(),
+    };
+
+    let mut child = command
+        .stdin(Stdio::null())
+        .stdout(stdout)
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| command_error(command, None, e.to_string()))?;
+
+    let stderr_thread = child.stderr.take().map(|mut child_stderr| thread::spawn(move || {
+        let mut captured = Vec::new();
+        let mut buffer = [0; 4096];
+
+        while let Ok(length) = child_stderr.read(&mut buffer) {
+            if length == 0 {
+                break;
+            }
+
+            let _ = io::stderr().write_all(&buffer[..length]);
+            captured.extend_from_slice(&buffer[..length]);
+        }
+
+        captured
+    }));
+
+    // Standard output is read while standard error is forwarded, so neither pipe can fill up and
+    // block the command.
+    let mut output = Vec::new();
+
+    if let Some(mut child_stdout) = child.stdout.take() {
+        child_stdout.read_to_end(&mut output)
+            .map_err(|e| command_error(command, None, e.to_string()))?;
+    }
+
+    let status = child.wait()
+        .map_err(|e| command_error(command, None, e.to_string()))?;
+
+    let stderr = stderr_thread
+        .and_then(|x| x.join().ok())
+        .unwrap_or_default();
+
+    if !status.success() {
+        return Err(command_error(command, status.code(), String::from_utf8_lossy(&stderr).to_string()));
+    }
+
+    Ok(String::from_utf8_lossy(&output).to_string())
+}
+
+/// Run a command to completion, returning an error if it could not be run or exited
+/// unsuccessfully.
+pub (crate) fn run(command: &mut Command) -> Result<(), CommandError> {
+    execute(command, false)
+        .map(|_| ())
+}
+
+/// Run a command to completion, capturing its standard output.
+#[cfg_attr(feature = "gix", allow(dead_code))]
+pub (crate) fn run_output(command: &mut Command) -> Result<String, CommandError> {
+    execute(command, true)
+}
+
+#[cfg(test)]
+#[cfg(unix)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn captures_standard_output() {
+        let output = execute(Command::new("sh").arg("-c").arg("echo out; echo err >&2"), true);
+
+        assert_eq!(output.unwrap(), "out\n");
+    }
+
+    #[test]
+    fn reports_status_and_standard_error() {
+        let error = run(Command::new("sh").arg("-c").arg("echo err >&2; exit 3")).unwrap_err();
+
+        assert_eq!(error.status, Some(3));
+        assert_eq!(error.stderr, "err\n");
+        assert_eq!(error.command, "sh -c echo err >&2; exit 3");
+    }
+
+    #[test]
+    fn reports_commands_that_can_not_run() {
+        let error = run(&mut Command::new("bind-builder-missing-command")).unwrap_err();
+
+        assert_eq!(error.status, None);
+    }
+}
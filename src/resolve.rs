@@ -0,0 +1,215 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use crate::error::Error;
+use crate::import_library::{is_import_library, read_dll_name};
+use crate::types::link::{LinkKind, LinkModifiers, LinkTarget};
+use crate::variables::{platform, shared_library_extension, Platform};
+use crate::version::compare_versions;
+use crate::{get_import_library_names, get_shared_library_name, get_static_library_name, LIBRARY_NAME_PREFIX};
+
+/// Type of library file a link target resolved to.
+#[derive(Clone, Copy, PartialEq)]
+pub (crate) enum ArtifactKind {
+    Static,
+    Shared,
+    Framework,
+}
+
+/// A link target and the single library file it resolved to.
+pub (crate) struct ResolvedTarget {
+    pub (crate) name: String,
+    pub (crate) kind: ArtifactKind,
+    pub (crate) modifiers: LinkModifiers,
+
+    /// Location of the library, `None` for system frameworks.
+    pub (crate) path: Option<PathBuf>,
+
+    /// Location of the file loaded at runtime for shared libraries, this is the DLL for import
+    /// libraries on Windows. `None` if it could not be found.
+    pub (crate) runtime_path: Option<PathBuf>,
+}
+
+/// Find the library file for a link target.
+///
+/// Directories are searched in order and the first directory containing the library wins, within
+/// a directory the preferred type of library given by the link kind is chosen first. If nothing
+/// is found the error lists every path that was tried.
+///
+/// On Windows shared libraries are linked through their import library, which can have the same
+/// name as a static library and is told apart by its contents. The DLL is then looked for in the
+/// runtime directories followed by the library directories.
+pub (crate) fn resolve_link_target(
+    target: &LinkTarget,
+    library_directories: &[PathBuf],
+    runtime_directories: &[PathBuf],
+) -> Result<ResolvedTarget, Error> {
+    let name = target.name.as_str();
+    let mut modifiers = target.modifiers;
+
+    if target.kind == LinkKind::StaticWholeArchive {
+        modifiers.whole_archive(true);
+    }
+
+    let candidates = match target.modifiers.is_verbatim() {
+        // Verbatim targets are full file names, the type of library is given by the extension
+        // unless the link kind asks for a specific one.
+        true => {
+            let kind = match target.kind {
+                LinkKind::Static | LinkKind::StaticWholeArchive => ArtifactKind::Static,
+                LinkKind::Dynamic => ArtifactKind::Shared,
+                LinkKind::Framework => ArtifactKind::Framework,
+                LinkKind::PreferStatic | LinkKind::PreferDynamic => {
+                    match name.ends_with(".a") || name.ends_with(".lib") {
+                        true => ArtifactKind::Static,
+                        false => ArtifactKind::Shared,
+                    }
+                },
+            };
+
+            vec![(name.to_string(), kind)]
+        },
+        false => {
+            let static_libraries = vec![(get_static_library_name(name)?, ArtifactKind::Static)];
+
+            let shared_libraries = match platform()? {
+                Platform::Windows => get_import_library_names(name)?,
+                _ => vec![get_shared_library_name(name)?],
+            }
+                .into_iter()
+                .map(|x| (x, ArtifactKind::Shared))
+                .collect::<Vec<(String, ArtifactKind)>>();
+
+            match target.kind {
+                LinkKind::PreferStatic => [static_libraries, shared_libraries].concat(),
+                LinkKind::PreferDynamic => [shared_libraries, static_libraries].concat(),
+                LinkKind::Static | LinkKind::StaticWholeArchive => static_libraries,
+                LinkKind::Dynamic => shared_libraries,
+                LinkKind::Framework => vec![(format!("{}.framework", name), ArtifactKind::Framework)],
+            }
+        },
+    };
+
+    let mut searched = Vec::new();
+
+    for library_directory in library_directories.iter() {
+        for (file_name, kind) in candidates.iter() {
+            let path = library_directory.join(file_name);
+
+            let exists = match kind {
+                ArtifactKind::Framework => path.is_dir(),
+                ArtifactKind::Static | ArtifactKind::Shared if platform()? == Platform::Windows => {
+                    path.is_file() && is_import_library(path.as_path())? == (*kind == ArtifactKind::Shared)
+                },
+                _ => path.is_file(),
+            };
+
+            if exists {
+                let runtime_path = match kind {
+                    ArtifactKind::Shared if platform()? == Platform::Windows => {
+                        find_dll(name, path.as_path(), &[runtime_directories, library_directories].concat())?
+                    },
+                    ArtifactKind::Shared => Some(path.clone()),
+                    _ => None,
+                };
+
+                return Ok(ResolvedTarget {
+                    name: name.to_string(),
+                    kind: *kind,
+                    modifiers,
+                    path: Some(path),
+                    runtime_path,
+                });
+            }
+
+            searched.push(path);
+        }
+
+        // Runtime packages may only ship the versioned `libfoo.so.1` without the unversioned
+        // development symlink, link it by its full file name.
+        let accepts_shared = candidates.iter().any(|(_, kind)| *kind == ArtifactKind::Shared);
+
+        if accepts_shared && !modifiers.is_verbatim() && shared_library_extension()? == ".so" {
+            let prefix = format!("{}.", get_shared_library_name(name)?);
+
+            if let Some(path) = find_versioned_library(library_directory, prefix.as_str())? {
+                let mut modifiers = modifiers;
+                modifiers.verbatim(true);
+
+                return Ok(ResolvedTarget {
+                    name: path.file_name().unwrap().to_string_lossy().to_string(),
+                    kind: ArtifactKind::Shared,
+                    modifiers,
+                    path: Some(path.clone()),
+                    runtime_path: Some(path),
+                });
+            }
+
+            searched.push(library_directory.join(format!("{}*", prefix)));
+        }
+    }
+
+    // Frameworks that are not bundled are provided by the system.
+    if target.kind == LinkKind::Framework {
+        return Ok(ResolvedTarget {
+            name: name.to_string(),
+            kind: ArtifactKind::Framework,
+            modifiers,
+            path: None,
+            runtime_path: None,
+        });
+    }
+
+    Err(Error::LinkTargetNotFound {
+        name: name.to_string(),
+        searched,
+    })
+}
+
+/// Find the file with the highest version that starts with `prefix`, such as `libfoo.so.1.2`.
+fn find_versioned_library(
+    library_directory: &Path,
+    prefix: &str,
+) -> Result<Option<PathBuf>, Error> {
+    let mut versions = fs::read_dir(library_directory)?
+        .filter_map(|x| x.ok())
+        .map(|x| x.path())
+        .filter(|x| x.is_file())
+        .filter_map(|x| {
+            let version = x.file_name()?.to_str()?.strip_prefix(prefix)?.to_string();
+            Some((version, x))
+        })
+        .collect::<Vec<(String, PathBuf)>>();
+
+    versions.sort_by(|(a, _), (b, _)| compare_versions(a, b));
+
+    Ok(versions.pop().map(|(_, x)| x))
+}
+
+/// Find the DLL for an import library, using the name stored in the import library and falling
+/// back to the usual names for the link target.
+fn find_dll(
+    name: &str,
+    import_library: &Path,
+    directories: &[PathBuf],
+) -> Result<Option<PathBuf>, Error> {
+    let mut file_names = Vec::new();
+
+    if let Some(dll_name) = read_dll_name(import_library)? {
+        file_names.push(dll_name);
+    }
+
+    // MinGW DLLs are usually prefixed while MSVC ones are not, but both are common.
+    for file_name in [
+        get_shared_library_name(name)?,
+        format!("{}{}", name, shared_library_extension()?),
+        format!("{}{}{}", LIBRARY_NAME_PREFIX, name, shared_library_extension()?),
+    ] {
+        if !file_names.contains(&file_name) {
+            file_names.push(file_name);
+        }
+    }
+
+    Ok(file_names.iter()
+        .flat_map(|x| directories.iter().map(move |directory| directory.join(x)))
+        .find(|x| x.is_file()))
+}
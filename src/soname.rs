@@ -0,0 +1,360 @@
+use std::fs;
+use std::path::Path;
+use crate::error::Error;
+
+const ELF_MAGIC: &[u8] = b"\x7fELF";
+
+const PT_LOAD: u32 = 1;
+const PT_DYNAMIC: u32 = 2;
+
+const DT_NULL: u64 = 0;
+const DT_STRTAB: u64 = 5;
+const DT_SONAME: u64 = 14;
+
+const MH_MAGIC: u32 = 0xfeedface;
+const MH_MAGIC_64: u32 = 0xfeedfacf;
+const FAT_MAGIC: u32 = 0xcafebabe;
+const FAT_MAGIC_64: u32 = 0xcafebabf;
+
+const LC_ID_DYLIB: u32 = 0xd;
+
+/// Bounds checked reads of integers with a given byte order.
+struct Reader<'a> {
+    data: &'a [u8],
+    big_endian: bool,
+}
+
+impl Reader<'_> {
+
+    fn bytes<const N: usize>(&self, offset: u64) -> Option<[u8; N]> {
+        let offset = usize::try_from(offset).ok()?;
+
+        self.data.get(offset..offset.checked_add(N)?)
+            .map(|x| x.try_into().unwrap())
+    }
+
+    fn u16(&self, offset: u64) -> Option<u16> {
+        let bytes = self.bytes(offset)?;
+        Some(if self.big_endian { u16::from_be_bytes(bytes) } else { u16::from_le_bytes(bytes) })
+    }
+
+    fn u32(&self, offset: u64) -> Option<u32> {
+        let bytes = self.bytes(offset)?;
+        Some(if self.big_endian { u32::from_be_bytes(bytes) } else { u32::from_le_bytes(bytes) })
+    }
+
+    fn u64(&self, offset: u64) -> Option<u64> {
+        let bytes = self.bytes(offset)?;
+        Some(if self.big_endian { u64::from_be_bytes(bytes) } else { u64::from_le_bytes(bytes) })
+    }
+
+    /// Read an address sized word, 8 bytes for 64 bit files and 4 bytes otherwise.
+    fn word(&self, offset: u64, is_64: bool) -> Option<u64> {
+        match is_64 {
+            true => self.u64(offset),
+            false => self.u32(offset).map(u64::from),
+        }
+    }
+
+    fn string(&self, offset: u64) -> Option<String> {
+        let offset = usize::try_from(offset).ok()?;
+        let bytes = self.data.get(offset..)?;
+        let end = bytes.iter().position(|x| *x == 0)?;
+
+        String::from_utf8(bytes[..end].to_vec()).ok()
+    }
+}
+
+/// Read the name a shared library is loaded by at runtime.
+///
+/// This is `DT_SONAME` for ELF files and `LC_ID_DYLIB` for Mach-O files, such as `libfoo.so.1`
+/// or `@rpath/libfoo.1.dylib`. Returns `None` for other files or libraries without such a name.
+pub (crate) fn read_soname(path: &Path) -> Result<Option<String>, Error> {
+    let data = fs::read(path)?;
+
+    if data.starts_with(ELF_MAGIC) {
+        return Ok(read_elf_soname(data.as_slice()));
+    }
+
+    Ok(read_mach_o_install_name(data.as_slice()))
+}
+
+fn read_elf_soname(data: &[u8]) -> Option<String> {
+    let is_64 = *data.get(4)? == 2;
+    let reader = Reader {
+        data,
+        big_endian: *data.get(5)? == 2,
+    };
+
+    let (program_headers, entry_size, entry_count) = match is_64 {
+        true => (reader.u64(0x20)?, reader.u16(0x36)?, reader.u16(0x38)?),
+        false => (reader.u32(0x1c)? as u64, reader.u16(0x2a)?, reader.u16(0x2c)?),
+    };
+
+    // Offset, virtual address and file size of each segment.
+    let mut loads = Vec::new();
+    let mut dynamic = None;
+
+    for index in 0..entry_count as u64 {
+        let header = program_headers.checked_add(index * entry_size as u64)?;
+
+        let (offset, address, size) = match is_64 {
+            true => (reader.u64(header + 8)?, reader.u64(header + 16)?, reader.u64(header + 32)?),
+            false => (reader.u32(header + 4)? as u64, reader.u32(header + 8)? as u64, reader.u32(header + 16)? as u64),
+        };
+
+        match reader.u32(header)? {
+            PT_LOAD => loads.push((offset, address, size)),
+            PT_DYNAMIC => dynamic = Some(offset),
+            _ => {},
+        }
+    }
+
+    let mut string_table = None;
+    let mut soname = None;
+
+    let entry_size = if is_64 { 16 } else { 8 };
+    let mut entry = dynamic?;
+
+    loop {
+        let tag = reader.word(entry, is_64)?;
+        let value = reader.word(entry + entry_size / 2, is_64)?;
+
+        match tag {
+            DT_NULL => break,
+            DT_STRTAB => string_table = Some(value),
+            DT_SONAME => soname = Some(value),
+            _ => {},
+        }
+
+        entry += entry_size;
+    }
+
+    // The string table is given as a virtual address, map it back to a file offset.
+    let string_table = string_table?;
+    let string_table = loads.iter()
+        .find(|(_, address, size)| (*address..address + size).contains(&string_table))
+        .map(|(offset, address, _)| string_table - address + offset)?;
+
+    reader.string(string_table.checked_add(soname?)?)
+}
+
+fn read_mach_o_install_name(data: &[u8]) -> Option<String> {
+    let magic = Reader { data, big_endian: true }.u32(0)?;
+
+    // Universal binaries contain one Mach-O file per architecture, they all share the same name.
+    if magic == FAT_MAGIC || magic == FAT_MAGIC_64 {
+        let reader = Reader { data, big_endian: true };
+
+        let offset = match magic {
+            FAT_MAGIC => reader.u32(8 + 8)? as u64,
+            _ => reader.u64(8 + 8)?,
+        };
+
+        return read_mach_o_install_name(data.get(usize::try_from(offset).ok()?..)?);
+    }
+
+    let (big_endian, is_64) = match magic {
+        MH_MAGIC => (true, false),
+        MH_MAGIC_64 => (true, true),
+        _ if magic.swap_bytes() == MH_MAGIC => (false, false),
+        _ if magic.swap_bytes() == MH_MAGIC_64 => (false, true),
+        _ => return None,
+    };
+
+    let reader = Reader { data, big_endian };
+
+    let command_count = reader.u32(16)?;
+    let mut command = if is_64 { 32 } else { 28 };
+
+    for _ in 0..command_count {
+        let kind = reader.u32(command)?;
+        let size = reader.u32(command + 4)?;
+
+        if kind == LC_ID_DYLIB {
+            let name = reader.u32(command + 8)?;
+            return reader.string(command + name as u64);
+        }
+
+        if size == 0 {
+            return None;
+        }
+
+        command += size as u64;
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_directory::TestDirectory;
+
+    const LC_SEGMENT_64: u64 = 0x19;
+
+    /// Write an integer of `size` bytes at `offset`, growing the buffer as needed.
+    fn put(
+        data: &mut Vec<u8>,
+        offset: usize,
+        value: u64,
+        size: usize,
+        big_endian: bool,
+    ) {
+        let bytes = match big_endian {
+            true => value.to_be_bytes()[8 - size..].to_vec(),
+            false => value.to_le_bytes()[..size].to_vec(),
+        };
+
+        if data.len() < offset + size {
+            data.resize(offset + size, 0);
+        }
+
+        data[offset..offset + size].copy_from_slice(bytes.as_slice());
+    }
+
+    /// Shared library with a `PT_LOAD` segment mapping the whole file at 0x1000 and a dynamic
+    /// section referring to a string table holding the SONAME.
+    fn elf(
+        is_64: bool,
+        big_endian: bool,
+        soname: Option<&str>,
+    ) -> Vec<u8> {
+        let (header_size, program_header_size, word) = if is_64 { (64, 56, 8) } else { (52, 32, 4) };
+        let address = 0x1000;
+        let dynamic = header_size + 2 * program_header_size;
+        let strings = dynamic + 3 * 2 * word;
+
+        let mut data = ELF_MAGIC.to_vec();
+        data.push(if is_64 { 2 } else { 1 });
+        data.push(if big_endian { 2 } else { 1 });
+
+        match is_64 {
+            true => {
+                put(&mut data, 0x20, header_size as u64, 8, big_endian);
+                put(&mut data, 0x36, program_header_size as u64, 2, big_endian);
+                put(&mut data, 0x38, 2, 2, big_endian);
+            },
+            false => {
+                put(&mut data, 0x1c, header_size as u64, 4, big_endian);
+                put(&mut data, 0x2a, program_header_size as u64, 2, big_endian);
+                put(&mut data, 0x2c, 2, 2, big_endian);
+            },
+        }
+
+        let segments = [
+            (PT_LOAD, 0, address, strings + 64),
+            (PT_DYNAMIC, dynamic, address + dynamic, 3 * 2 * word),
+        ];
+
+        for (index, (kind, offset, virtual_address, size)) in segments.into_iter().enumerate() {
+            let header = header_size + index * program_header_size;
+            put(&mut data, header, kind as u64, 4, big_endian);
+
+            // The 64 bit header moves the flags before the offset.
+            let fields = if is_64 { header + 8 } else { header + 4 };
+            put(&mut data, fields, offset as u64, word, big_endian);
+            put(&mut data, fields + word, virtual_address as u64, word, big_endian);
+            put(&mut data, fields + 3 * word, size as u64, word, big_endian);
+        }
+
+        let mut entries = vec![(DT_STRTAB, (address + strings) as u64)];
+
+        if soname.is_some() {
+            entries.push((DT_SONAME, 1));
+        }
+
+        entries.push((DT_NULL, 0));
+
+        for (index, (tag, value)) in entries.into_iter().enumerate() {
+            put(&mut data, dynamic + index * 2 * word, tag, word, big_endian);
+            put(&mut data, dynamic + index * 2 * word + word, value, word, big_endian);
+        }
+
+        data.resize(strings + 1, 0);
+        data.extend(soname.unwrap_or_default().bytes());
+        data.resize(strings + 64, 0);
+        data
+    }
+
+    /// Dynamic library with a segment command followed by `LC_ID_DYLIB`.
+    fn mach_o(
+        is_64: bool,
+        big_endian: bool,
+        install_name: &str,
+    ) -> Vec<u8> {
+        let (magic, header_size) = if is_64 { (MH_MAGIC_64, 32) } else { (MH_MAGIC, 28) };
+        let id_size = (24 + install_name.len() + 1).next_multiple_of(8);
+
+        let mut data = Vec::new();
+        put(&mut data, 0, magic as u64, 4, big_endian);
+        put(&mut data, 16, 2, 4, big_endian);
+
+        put(&mut data, header_size, LC_SEGMENT_64, 4, big_endian);
+        put(&mut data, header_size + 4, 16, 4, big_endian);
+
+        let id = header_size + 16;
+        put(&mut data, id, LC_ID_DYLIB as u64, 4, big_endian);
+        put(&mut data, id + 4, id_size as u64, 4, big_endian);
+        put(&mut data, id + 8, 24, 4, big_endian);
+
+        data.resize(id + 24, 0);
+        data.extend(install_name.bytes());
+        data.resize(id + id_size, 0);
+        data
+    }
+
+    #[test]
+    fn reads_elf_sonames() {
+        for (is_64, big_endian) in [(true, false), (true, true), (false, false), (false, true)] {
+            let data = elf(is_64, big_endian, Some("libfoo.so.1"));
+
+            assert_eq!(read_elf_soname(data.as_slice()), Some("libfoo.so.1".to_string()));
+        }
+
+        assert_eq!(read_elf_soname(elf(true, false, None).as_slice()), None);
+    }
+
+    #[test]
+    fn reads_mach_o_install_names() {
+        for (is_64, big_endian) in [(true, false), (false, true)] {
+            let data = mach_o(is_64, big_endian, "@rpath/libfoo.1.dylib");
+
+            assert_eq!(read_mach_o_install_name(data.as_slice()), Some("@rpath/libfoo.1.dylib".to_string()));
+        }
+
+        // Universal binary with a single architecture stored after the header.
+        let mut fat = Vec::new();
+        put(&mut fat, 0, FAT_MAGIC as u64, 4, true);
+        put(&mut fat, 4, 1, 4, true);
+        put(&mut fat, 16, 64, 4, true);
+        fat.resize(64, 0);
+        fat.extend(mach_o(true, false, "/usr/lib/libfoo.dylib"));
+
+        assert_eq!(read_mach_o_install_name(fat.as_slice()), Some("/usr/lib/libfoo.dylib".to_string()));
+    }
+
+    #[test]
+    fn ignores_malformed_files() {
+        let data = elf(true, false, Some("libfoo.so.1"));
+
+        for length in [0, 5, 64, 150, 200, 230] {
+            assert_eq!(read_elf_soname(&data[..length]), None);
+        }
+
+        // A zero sized load command would otherwise loop forever.
+        let mut data = mach_o(true, false, "libfoo.dylib");
+        put(&mut data, 36, 0, 4, false);
+
+        assert_eq!(read_mach_o_install_name(data.as_slice()), None);
+        assert_eq!(read_mach_o_install_name(b"!<arch>\n"), None);
+    }
+
+    #[test]
+    fn reads_sonames_from_files() {
+        let directory = TestDirectory::new("soname");
+        let path = directory.write("libfoo.so", elf(true, false, Some("libfoo.so.1")));
+
+        assert_eq!(read_soname(path.as_path()).unwrap(), Some("libfoo.so.1".to_string()));
+    }
+}
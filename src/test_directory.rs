@@ -0,0 +1,58 @@
+use std::env;
+use std::fs;
+use std::ops::Deref;
+use std::path::{Path, PathBuf};
+
+/// An empty directory for test fixtures, removed again when dropped so that failing tests do
+/// not leave it behind either.
+pub (crate) struct TestDirectory {
+    path: PathBuf,
+}
+
+impl TestDirectory {
+
+    /// Create `bind-builder-<name>-<pid>` in the system temporary directory, replacing what a
+    /// previous run left there.
+    pub (crate) fn new(name: &str) -> TestDirectory {
+        let path = env::temp_dir().join(format!("bind-builder-{}-{}", name, std::process::id()));
+
+        let _ = fs::remove_dir_all(path.as_path());
+        fs::create_dir_all(path.as_path()).unwrap();
+
+        TestDirectory {
+            path,
+        }
+    }
+
+    pub (crate) fn as_path(&self) -> &Path {
+        self.path.as_path()
+    }
+
+    /// Write a file relative to the directory, creating its parent directories.
+    pub (crate) fn write(
+        &self,
+        name: &str,
+        contents: impl AsRef<[u8]>,
+    ) -> PathBuf {
+        let path = self.path.join(name);
+
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path.as_path(), contents).unwrap();
+
+        path
+    }
+}
+
+impl Deref for TestDirectory {
+    type Target = Path;
+
+    fn deref(&self) -> &Path {
+        self.path.as_path()
+    }
+}
+
+impl Drop for TestDirectory {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(self.path.as_path());
+    }
+}
@@ -0,0 +1,108 @@
+use std::path::PathBuf;
+
+/// Type of a target in a CMake project.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TargetKind {
+    Executable,
+    StaticLibrary,
+    SharedLibrary,
+    ModuleLibrary,
+    ObjectLibrary,
+    InterfaceLibrary,
+    Utility,
+}
+
+impl TargetKind {
+
+    pub (crate) fn from_file_api(kind: &str) -> Option<TargetKind> {
+        match kind {
+            "EXECUTABLE" => Some(TargetKind::Executable),
+            "STATIC_LIBRARY" => Some(TargetKind::StaticLibrary),
+            "SHARED_LIBRARY" => Some(TargetKind::SharedLibrary),
+            "MODULE_LIBRARY" => Some(TargetKind::ModuleLibrary),
+            "OBJECT_LIBRARY" => Some(TargetKind::ObjectLibrary),
+            "INTERFACE_LIBRARY" => Some(TargetKind::InterfaceLibrary),
+            "UTILITY" => Some(TargetKind::Utility),
+            _ => None,
+        }
+    }
+
+    /// Whether the target produces a library that can be linked against.
+    pub fn is_linkable(&self) -> bool {
+        matches!(self, TargetKind::StaticLibrary | TargetKind::SharedLibrary)
+    }
+}
+
+/// A target of a built CMake project.
+#[derive(Clone, Debug)]
+pub struct BuildTarget {
+    pub (crate) name: String,
+    pub (crate) kind: TargetKind,
+
+    pub (crate) artifacts: Vec<PathBuf>,
+    pub (crate) include_directories: Vec<PathBuf>,
+
+    pub (crate) dependencies: Vec<String>,
+    pub (crate) link_libraries: Vec<String>,
+}
+
+impl BuildTarget {
+
+    /// Name of the target.
+    pub fn get_name(&self) -> &String {
+        &self.name
+    }
+
+    /// Type of the target.
+    pub fn get_kind(&self) -> TargetKind {
+        self.kind
+    }
+
+    /// Absolute paths of the files produced by the target.
+    pub fn get_artifacts(&self) -> &Vec<PathBuf> {
+        &self.artifacts
+    }
+
+    /// Include directories used to compile the target.
+    pub fn get_include_directories(&self) -> &Vec<PathBuf> {
+        &self.include_directories
+    }
+
+    /// Names of the targets in the project that this target depends on.
+    pub fn get_dependencies(&self) -> &Vec<String> {
+        &self.dependencies
+    }
+
+    /// Libraries passed to the linker when linking the target.
+    ///
+    /// This is only available for executables and shared libraries, since static libraries are
+    /// not linked.
+    pub fn get_link_libraries(&self) -> &Vec<String> {
+        &self.link_libraries
+    }
+}
+
+/// Targets and artifacts of a built CMake project, read from the CMake File API.
+#[derive(Clone, Debug)]
+pub struct BuildInfo {
+    pub (crate) build_directory: PathBuf,
+    pub (crate) targets: Vec<BuildTarget>,
+}
+
+impl BuildInfo {
+
+    /// Build directory of the project.
+    pub fn get_build_directory(&self) -> &PathBuf {
+        &self.build_directory
+    }
+
+    /// All targets of the project.
+    pub fn get_targets(&self) -> &Vec<BuildTarget> {
+        &self.targets
+    }
+
+    /// Find a target by name.
+    pub fn get_target(&self, name: &str) -> Option<&BuildTarget> {
+        self.targets.iter().find(|x| x.name == name)
+    }
+}
@@ -0,0 +1,91 @@
+/// Which submodules of a repository are checked out.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub enum Submodules {
+    /// Every submodule, recursively.
+    #[default]
+    All,
+
+    /// No submodules.
+    None,
+
+    /// Only the submodules at these paths, and their own submodules.
+    Paths(Vec<String>),
+}
+
+/// Options for cloning a git repository, to avoid fetching history and files that are not needed
+/// to build large projects.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct CloneOptions {
+    pub (crate) revision: Option<String>,
+    pub (crate) depth: Option<u32>,
+    pub (crate) filter: Option<String>,
+    pub (crate) sparse_paths: Vec<String>,
+    pub (crate) submodules: Submodules,
+}
+
+impl CloneOptions {
+
+    /// Create a new `CloneOptions` instance that fetches the full history and checks out every
+    /// file and submodule.
+    pub fn new() -> CloneOptions {
+        CloneOptions::default()
+    }
+
+    /// Full SHA of the commit the tag is expected to point to, cloning fails if it does not.
+    /// Abbreviated hashes are rejected before cloning.
+    pub fn revision(
+        &mut self,
+        revision: &str,
+    ) -> &mut CloneOptions {
+        self.revision = Some(revision.to_string());
+        self
+    }
+
+    /// Only fetch the last `depth` commits, `1` fetches the tag without any history.
+    ///
+    /// This also applies to submodules.
+    pub fn depth(
+        &mut self,
+        depth: u32,
+    ) -> &mut CloneOptions {
+        self.depth = Some(depth);
+        self
+    }
+
+    /// Make a partial clone, for example `blob:none` only downloads the contents of files that are
+    /// checked out. The remote must support partial clones.
+    ///
+    /// This also applies to submodules. Filters are ignored with the `gix` feature, which fetches
+    /// every object instead.
+    pub fn filter(
+        &mut self,
+        filter: &str,
+    ) -> &mut CloneOptions {
+        self.filter = Some(filter.to_string());
+        self
+    }
+
+    /// Only check out this directory, relative to the root of the repository. Files in the root
+    /// directory are always checked out.
+    pub fn sparse_path(
+        &mut self,
+        path: &str,
+    ) -> &mut CloneOptions {
+        self.sparse_paths.push(path.to_string());
+        self
+    }
+
+    /// Choose the submodules that are checked out, defaults to all of them.
+    pub fn submodules(
+        &mut self,
+        submodules: Submodules,
+    ) -> &mut CloneOptions {
+        self.submodules = submodules;
+        self
+    }
+
+    /// Finalize the `CloneOptions` configuration.
+    pub fn get(&self) -> CloneOptions {
+        self.clone()
+    }
+}
@@ -1,22 +1,260 @@
-use std::{env, fs};
+use std::{env, fs, panic};
+use std::fs::{File, TryLockError};
+use std::any::Any;
 use std::ffi::OsStr;
+use std::panic::AssertUnwindSafe;
 use std::path::{Path, PathBuf};
 use std::process::Command;
+use std::time::UNIX_EPOCH;
 use cmake::Config;
-use crate::variables::{get_profile, target_directory};
+use crate::commands::{print_warning, rerun_if_changed, rerun_if_env_changed, utf8_path};
+use crate::error::{unwrap_or_panic, Error};
+use crate::download;
+use crate::file_api;
+use crate::git;
+use crate::glob;
+use crate::hash::Sha256;
+use crate::process::run;
+use crate::types::build_info::BuildInfo;
+use crate::types::clone_options::CloneOptions;
+use crate::variables::{cache_directory, get_profile, out_directory, shared_target_directory, target, target_env_var_names};
+
+/// Environment variables read by this crate or the cmake crate when configuring a project.
+const CMAKE_ENV_VARIABLES: [&str; 5] = [
+    "CMAKE",
+    "CMAKE_GENERATOR",
+    "CMAKE_TOOLCHAIN_FILE",
+    "CMAKE_PREFIX_PATH",
+    "CMAKE_BUILD_PARALLEL_LEVEL",
+];
+
+/// Environment variables of the compilers and tools used by CMake.
+const TOOLCHAIN_ENV_VARIABLES: [&str; 7] = [
+    "CC",
+    "CXX",
+    "AR",
+    "CFLAGS",
+    "CXXFLAGS",
+    "ASMFLAGS",
+    "LDFLAGS",
+];
+
+/// Environment variables set by cargo that change how the project is built.
+const CARGO_ENV_VARIABLES: [&str; 5] = [
+    "TARGET",
+    "HOST",
+    "PROFILE",
+    "OPT_LEVEL",
+    "DEBUG",
+];
+
+/// File in the configure directory holding the fingerprint of the last successful build.
+const STAMP_FILE_NAME: &str = "bind-builder.stamp";
+
+/// Paths that are never part of the sources of a project, relative to the source directory.
+/// `cmake-bind-builder-*` covers build directories left in the sources by older versions.
+const DEFAULT_RERUN_IGNORES: [&str; 2] = [
+    ".git",
+    "cmake-bind-builder-*",
+];
+
+/// Directory a project is configured, built and installed in unless set with `out_directory`.
+///
+/// It is kept in `OUT_DIR` so that the sources are never modified, they may be a git submodule,
+/// read-only or a crate in the cargo registry.
+fn default_out_directory(name: &str) -> Result<PathBuf, Error> {
+    Ok(out_directory()?.join(format!("cmake-bind-builder-{}", name)))
+}
+
+/// Every environment variable that is part of the configuration hash, with the per target names
+/// of the toolchain and CMake variables.
+fn hashed_env_variables() -> Result<Vec<String>, Error> {
+    let mut variables = Vec::new();
+
+    for name in TOOLCHAIN_ENV_VARIABLES.iter().chain(CMAKE_ENV_VARIABLES.iter()) {
+        variables.append(&mut target_env_var_names(name)?);
+    }
+
+    variables.extend(CARGO_ENV_VARIABLES.iter().map(|x| x.to_string()));
+    Ok(variables)
+}
+
+/// Resolve a path given to the builder, relative paths are relative to the package being built.
+fn resolve_path(path: &Path) -> PathBuf {
+    env::current_dir()
+        .map(|x| x.join(path))
+        .unwrap_or(path.to_path_buf())
+}
 
 fn cmake_executable() -> String {
     env::var("CMAKE")
         .unwrap_or_else(|_| String::from("cmake"))
 }
 
+/// Collect every file of a source tree, skipping paths matching `ignores`.
+///
+/// The paths to watch for changes are added to `watched`. Cargo scans a watched directory for
+/// changes to any file in it, so directories without ignored paths are watched as a whole and
+/// only the files of the others are watched one by one. Returns whether nothing was ignored.
+fn collect_sources(
+    source_directory: &Path,
+    directory: &Path,
+    ignores: &[String],
+    sources: &mut Vec<PathBuf>,
+    watched: &mut Vec<PathBuf>,
+) -> Result<bool, Error> {
+    let mut entries = fs::read_dir(directory)?
+        .filter_map(|x| x.ok())
+        .map(|x| x.path())
+        .collect::<Vec<PathBuf>>();
+
+    entries.sort();
+
+    let first_watched = watched.len();
+    let mut complete = true;
+
+    for path in entries {
+        let relative_path = path.strip_prefix(source_directory).unwrap()
+            .to_string_lossy()
+            .replace('\\', "/");
+
+        if ignores.iter().any(|x| glob::matches(x, relative_path.as_str())) {
+            complete = false;
+            continue;
+        }
+
+        match path.is_dir() {
+            true => complete &= collect_sources(source_directory, path.as_path(), ignores, sources, watched)?,
+            false => {
+                watched.push(path.clone());
+                sources.push(path);
+            },
+        }
+    }
+
+    if complete {
+        watched.truncate(first_watched);
+        watched.push(directory.to_path_buf());
+    }
+
+    Ok(complete)
+}
+
+/// Apply patches to the sources of a cloned or downloaded project and return their hashes.
+///
+/// Patches must be applied to pristine sources, or changing or removing one would leave the old
+/// version applied. Cloned sources are reset and cleaned on every clone. Sources extracted from
+/// `archive` are extracted again whenever the patches differ from the ones listed in its stamp,
+/// which is removed while patching so that a failed attempt is extracted again as well.
+fn patch_sources(
+    project_directory: &Path,
+    patches: &[PathBuf],
+    archive: Option<(&Path, &Path)>,
+) -> Result<Vec<String>, Error> {
+    let mut hashes = Vec::new();
+
+    for patch in patches.iter() {
+        hashes.push(download::sha256_file(patch.as_path())?);
+    }
+
+    let Some((archive_path, stamp_path)) = archive else {
+        for patch in patches.iter() {
+            git::apply_patch(project_directory, patch.as_path())?;
+        }
+
+        return Ok(hashes);
+    };
+
+    let stamp = fs::read_to_string(stamp_path)?;
+    let mut lines = stamp.lines();
+    let sha256 = lines.next().unwrap_or_default().to_string();
+    let applied = lines.map(|x| x.to_string()).collect::<Vec<String>>();
+
+    if applied == hashes {
+        return Ok(hashes);
+    }
+
+    fs::remove_file(stamp_path)?;
+
+    if !applied.is_empty() {
+        fs::remove_dir_all(project_directory)?;
+        download::extract(archive_path, project_directory)?;
+    }
+
+    for patch in patches.iter() {
+        git::apply_patch(project_directory, patch.as_path())?;
+    }
+
+    let mut stamp = sha256;
+
+    for hash in hashes.iter() {
+        stamp.push('\n');
+        stamp.push_str(hash.as_str());
+    }
+
+    fs::write(stamp_path, stamp)?;
+    Ok(hashes)
+}
+
+/// The source directory a build directory was configured for, read from `CMAKE_HOME_DIRECTORY`
+/// in its CMake cache.
+fn configured_source_directory(build_directory: &Path) -> Option<PathBuf> {
+    let cache = fs::read_to_string(build_directory.join("CMakeCache.txt")).ok()?;
+
+    cache.lines()
+        .find_map(|x| x.strip_prefix("CMAKE_HOME_DIRECTORY:INTERNAL="))
+        .map(PathBuf::from)
+}
+
+/// Whether two paths point to the same directory, paths that do not exist are never the same.
+fn is_same_directory(
+    a: &Path,
+    b: &Path,
+) -> bool {
+    match (fs::canonicalize(a), fs::canonicalize(b)) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => false,
+    }
+}
+
+/// Whether a revision is a full SHA-1 or SHA-256 commit hash rather than a tag or branch.
+fn is_commit_hash(revision: &str) -> bool {
+    (revision.len() == 40 || revision.len() == 64) && revision.chars().all(|x| x.is_ascii_hexdigit())
+}
+
+fn panic_message(payload: Box<dyn Any + Send>) -> String {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        return message.trim().to_string();
+    }
+
+    if let Some(message) = payload.downcast_ref::<String>() {
+        return message.trim().to_string();
+    }
+
+    "unknown error".to_string()
+}
+
 /// Builder for cloning, configuring, building and installing a CMake project.
 pub struct CMakeBuilder {
     name: String,
     cmake_config: Option<Config>,
     build_directory: Option<PathBuf>,
     install_directory: PathBuf,
-    build_target: Option<String>
+    custom_out_directory: bool,
+    build_target: Option<String>,
+    build_info: Option<BuildInfo>,
+
+    source_directory: Option<PathBuf>,
+    rerun_ignores: Vec<String>,
+
+    project_directory: Option<PathBuf>,
+    archive_path: Option<PathBuf>,
+    archive_stamp_path: Option<PathBuf>,
+    patches: Vec<PathBuf>,
+
+    url: Option<String>,
+    revision: Option<String>,
+    settings: Vec<String>,
 }
 
 impl CMakeBuilder {
@@ -24,73 +262,241 @@ impl CMakeBuilder {
     /// Create a new `CMakeBuilder` from a git repository.
     ///
     /// This function uses the git command therefore it will inherit the git configuration and
-    /// credentials from your system.
+    /// credentials from your system, or gitoxide with the `gix` feature.
+    ///
+    /// Panics if the repository could not be cloned, see `try_clone`.
     pub fn clone(
         name: &str,
         url: &str,
         tag: &str,
     ) -> CMakeBuilder {
+        unwrap_or_panic(CMakeBuilder::try_clone(name, url, tag))
+    }
+
+    /// Create a new `CMakeBuilder` from a git repository, returning an error if the repository
+    /// could not be cloned.
+    ///
+    /// When `tag` is a full commit SHA the checked out revision is verified against it.
+    pub fn try_clone(
+        name: &str,
+        url: &str,
+        tag: &str,
+    ) -> Result<CMakeBuilder, Error> {
+        let mut options = CloneOptions::new();
+
+        if is_commit_hash(tag) {
+            options.revision(tag);
+        }
+
+        CMakeBuilder::try_clone_with_options(name, url, tag, &options)
+    }
+
+    /// Create a new `CMakeBuilder` from a tag or branch of a git repository, pinned to the full SHA
+    /// of the commit it is expected to point to.
+    ///
+    /// Panics if the repository could not be cloned or the tag was moved to another commit, see
+    /// `try_clone_pinned`.
+    pub fn clone_pinned(
+        name: &str,
+        url: &str,
+        tag: &str,
+        revision: &str,
+    ) -> CMakeBuilder {
+        unwrap_or_panic(CMakeBuilder::try_clone_pinned(name, url, tag, revision))
+    }
+
+    /// Create a new `CMakeBuilder` from a tag or branch of a git repository, returning an error if
+    /// the repository could not be cloned or the tag does not point to `revision`.
+    pub fn try_clone_pinned(
+        name: &str,
+        url: &str,
+        tag: &str,
+        revision: &str,
+    ) -> Result<CMakeBuilder, Error> {
+        CMakeBuilder::try_clone_with_options(name, url, tag, CloneOptions::new().revision(revision))
+    }
+
+    /// Create a new `CMakeBuilder` from a git repository, with options for shallow, partial and
+    /// sparse clones.
+    ///
+    /// Panics if the repository could not be cloned, see `try_clone_with_options`.
+    pub fn clone_with_options(
+        name: &str,
+        url: &str,
+        tag: &str,
+        options: &CloneOptions,
+    ) -> CMakeBuilder {
+        unwrap_or_panic(CMakeBuilder::try_clone_with_options(name, url, tag, options))
+    }
+
+    /// Create a new `CMakeBuilder` from a git repository with options for shallow, partial and
+    /// sparse clones, returning an error if the repository could not be cloned.
+    pub fn try_clone_with_options(
+        name: &str,
+        url: &str,
+        tag: &str,
+        options: &CloneOptions,
+    ) -> Result<CMakeBuilder, Error> {
 
-        let target_directory = target_directory();
-        let clone_directory = target_directory.parent().unwrap()
+        // An abbreviated hash or a tag would never match the revision that is checked out.
+        if let Some(revision) = options.revision.as_ref().filter(|x| !is_commit_hash(x)) {
+            return Err(Error::InvalidRevision(revision.clone()));
+        }
+
+        let clone_directory = shared_target_directory()?
             .join("git")
             .join(name);
 
         // Setup temp repository if it does not exist, instead of cloning we do this to
         // reduce the amount of stuff we have to pull.
         if !clone_directory.exists() {
-            fs::create_dir_all(clone_directory.as_path())
-                .expect("Could not create directory, does the path exist?");
-
-            Command::new("git")
-                .arg("init")
-                .current_dir(clone_directory.as_path())
-                .status()
-                .expect("Could not init repo, is git installed?");
-
-            Command::new("git")
-                .arg("remote")
-                .arg("add")
-                .arg("origin")
-                .arg(url)
-                .current_dir(clone_directory.as_path())
-                .status()
-                .expect("Could not add remote, is git installed?");
-        }
-
-        Command::new("git")
-            .arg("fetch")
-            .arg("origin")
-            .arg(tag)
-            .current_dir(clone_directory.as_path())
-            .status()
-            .expect("Could not fetch repo, is git installed?");
-
-        Command::new("git")
-            .arg("reset")
-            .arg("--hard")
-            .arg(tag)
-            .current_dir(clone_directory.as_path())
-            .status()
-            .expect("Could not checkout tag, is git installed?");
-
-        Command::new("git")
-            .arg("submodule")
-            .arg("update")
-            .arg("--init")
-            .arg("--recursive")
-            .current_dir(clone_directory.as_path())
-            .status()
-            .expect("Could not init submodules, is git installed?");
-
-        CMakeBuilder::from(name, clone_directory.as_path())
+            fs::create_dir_all(clone_directory.as_path())?;
+            git::init(clone_directory.as_path(), url)?;
+        }
+
+        git::fetch(clone_directory.as_path(), tag, options)?;
+        git::sparse_checkout(clone_directory.as_path(), options.sparse_paths.as_slice())?;
+        git::reset(clone_directory.as_path(), "FETCH_HEAD")?;
+
+        // Patches are applied to the checked out files, start from a clean tree to apply them again.
+        git::clean(clone_directory.as_path())?;
+
+        let revision = git::head_revision(clone_directory.as_path())?;
+
+        if let Some(expected_revision) = &options.revision {
+            if !revision.eq_ignore_ascii_case(expected_revision) {
+                return Err(Error::RevisionMismatch {
+                    url: url.to_string(),
+                    expected: expected_revision.clone(),
+                    actual: revision,
+                });
+            }
+        }
+
+        git::update_submodules(clone_directory.as_path(), options)?;
+
+        let mut project = CMakeBuilder::try_from(name, clone_directory.as_path())?;
+
+        // The sources only change when the tag does, which means the build script changed. They
+        // belong to this crate, so they can be patched.
+        project.project_directory = project.source_directory.take();
+        project.url = Some(url.to_string());
+        project.revision = Some(revision);
+
+        // Sparse paths and submodules change the sources that are built.
+        for path in options.sparse_paths.iter() {
+            project.settings.push(format!("sparse_path {}", path));
+        }
+
+        project.settings.push(format!("submodules {:?}", options.submodules));
+
+        Ok(project)
+    }
+
+    /// Create a new `CMakeBuilder` from a source archive, such as a release tarball.
+    ///
+    /// `.tar.gz`, `.tar.xz`, `.tar.zst` and `.zip` archives are supported. The archive is
+    /// downloaded with the curl command and verified against its SHA-256 checksum before it is
+    /// extracted, a single top level directory in the archive is stripped.
+    ///
+    /// Panics if the archive could not be downloaded, verified or extracted, see `try_download`.
+    pub fn download(
+        name: &str,
+        url: &str,
+        sha256: &str,
+    ) -> CMakeBuilder {
+        unwrap_or_panic(CMakeBuilder::try_download(name, url, sha256))
+    }
+
+    /// Create a new `CMakeBuilder` from a source archive, returning an error if the archive could
+    /// not be downloaded, does not match the checksum or could not be extracted.
+    ///
+    /// The programs needed to download and extract the archive are checked before downloading,
+    /// `.tar.zst` archives also need the zstd command.
+    pub fn try_download(
+        name: &str,
+        url: &str,
+        sha256: &str,
+    ) -> Result<CMakeBuilder, Error> {
+
+        let download_directory = shared_target_directory()?
+            .join("download");
+
+        let sha256 = sha256.to_lowercase();
+        let extension = download::archive_extension(url)?;
+        let archive_path = download_directory.join(format!("{}{}", name, extension));
+        let extract_directory = download_directory.join(name);
+        let stamp_path = download_directory.join(format!("{}.sha256", name));
+
+        // The stamp holds the checksum of the archive that was extracted, followed by the hashes of
+        // the patches applied to it, and is only written once extracting succeeded.
+        let stamp = fs::read_to_string(stamp_path.as_path()).unwrap_or_default();
+
+        if stamp.lines().next().unwrap_or_default() != sha256 || !extract_directory.is_dir() {
+            fs::create_dir_all(download_directory.as_path())?;
+
+            let downloaded = archive_path.is_file()
+                && download::sha256_file(archive_path.as_path())? == sha256;
+
+            download::check_programs(extension, !downloaded)?;
+
+            if !downloaded {
+                download::download(url, archive_path.as_path())?;
+
+                let actual = download::sha256_file(archive_path.as_path())?;
+
+                if actual != sha256 {
+                    fs::remove_file(archive_path.as_path())?;
+
+                    return Err(Error::ChecksumMismatch {
+                        url: url.to_string(),
+                        expected: sha256,
+                        actual,
+                    });
+                }
+            }
+
+            if stamp_path.exists() {
+                fs::remove_file(stamp_path.as_path())?;
+            }
+
+            if extract_directory.exists() {
+                fs::remove_dir_all(extract_directory.as_path())?;
+            }
+
+            download::extract(archive_path.as_path(), extract_directory.as_path())?;
+            fs::write(stamp_path.as_path(), sha256.as_str())?;
+        }
+
+        let mut project = CMakeBuilder::try_from(name, extract_directory.as_path())?;
+
+        // The sources only change when the checksum does, which means the build script changed.
+        // They belong to this crate, so they can be patched.
+        project.project_directory = project.source_directory.take();
+        project.archive_path = Some(archive_path);
+        project.archive_stamp_path = Some(stamp_path);
+        project.url = Some(url.to_string());
+        project.revision = Some(sha256);
+
+        Ok(project)
     }
 
     /// Create a new `CMakeBuilder` from an existing cmake project.
+    ///
+    /// Panics if the project path does not exist, see `try_from`.
     pub fn from(
         name: &str,
         path: &Path,
     ) -> CMakeBuilder {
+        unwrap_or_panic(CMakeBuilder::try_from(name, path))
+    }
+
+    /// Create a new `CMakeBuilder` from an existing cmake project, returning an error if the
+    /// project path does not exist.
+    pub fn try_from(
+        name: &str,
+        path: &Path,
+    ) -> Result<CMakeBuilder, Error> {
 
         // Windows does not like canonicalize on some paths. It will result in cl.exe
         // failing to use the path.
@@ -99,35 +505,58 @@ impl CMakeBuilder {
         let absolute_path = if cfg!(windows) {
             path.to_path_buf()
         } else {
-            fs::canonicalize(path)
-                .expect("Path not found, make sure the build directory exists.")
+            fs::canonicalize(path)?
         };
 
-        let configure_directory = absolute_path
-            .join(format!("cmake-bind-builder-{}", get_profile().as_str()));
+        let configure_directory = default_out_directory(name)?;
 
         let install_directory = configure_directory
             .join("install");
 
         let mut project = CMakeBuilder {
             name: name.to_string(),
-            cmake_config: Some(Config::new(absolute_path)),
+            cmake_config: Some(Config::new(absolute_path.as_path())),
             build_directory: None,
             install_directory: install_directory.clone(),
-            build_target: None
+            custom_out_directory: false,
+            build_target: None,
+            build_info: None,
+
+            source_directory: Some(absolute_path),
+            rerun_ignores: DEFAULT_RERUN_IGNORES.iter().map(|x| x.to_string()).collect(),
+
+            project_directory: None,
+            archive_path: None,
+            archive_stamp_path: None,
+            patches: Vec::new(),
+
+            url: None,
+            revision: None,
+            settings: Vec::new(),
         };
 
         project.cmake_config.as_mut().unwrap().out_dir(configure_directory);
         project.cmake_config.as_mut().unwrap().define("CMAKE_SKIP_INSTALL_ALL_DEPENDENCY", "true");
 
-        project
+        Ok(project)
     }
 
     /// Create a new `CMakeBuilder` from an existing cmake build directory.
+    ///
+    /// Panics if the build directory does not exist, see `try_from_build_directory`.
     pub fn from_build_directory(
         name: &str,
         build_path: &Path,
     ) -> CMakeBuilder {
+        unwrap_or_panic(CMakeBuilder::try_from_build_directory(name, build_path))
+    }
+
+    /// Create a new `CMakeBuilder` from an existing cmake build directory, returning an error if
+    /// the build directory does not exist.
+    pub fn try_from_build_directory(
+        name: &str,
+        build_path: &Path,
+    ) -> Result<CMakeBuilder, Error> {
 
         // Windows does not like canonicalize on some paths. It will result in cl.exe
         // failing to use the path.
@@ -136,23 +565,33 @@ impl CMakeBuilder {
         let absolute_path = if cfg!(windows) {
             build_path.to_path_buf()
         } else {
-            fs::canonicalize(build_path)
-                .expect("Path not found, make sure the build directory exists.")
+            fs::canonicalize(build_path)?
         };
 
-        let install_directory = absolute_path
-            .join(format!("cmake-bind-builder-{}", get_profile().as_str()))
+        let install_directory = default_out_directory(name)?
             .join("install");
 
-        let project = CMakeBuilder {
+        Ok(CMakeBuilder {
             name: name.to_string(),
             cmake_config: None,
             build_directory: Some(absolute_path),
             install_directory: install_directory.clone(),
-            build_target: None
-        };
-
-        project
+            custom_out_directory: false,
+            build_target: None,
+            build_info: None,
+
+            source_directory: None,
+            rerun_ignores: Vec::new(),
+
+            project_directory: None,
+            archive_path: None,
+            archive_stamp_path: None,
+            patches: Vec::new(),
+
+            url: None,
+            revision: None,
+            settings: Vec::new(),
+        })
     }
 
     /// Sets the build-tool generator (`-G`) for this compilation.
@@ -161,6 +600,8 @@ impl CMakeBuilder {
     /// if set. Otherwise, it will guess the best generator to use based on the
     /// build target.
     pub fn generator<T: AsRef<OsStr>>(&mut self, generator: T) -> &mut CMakeBuilder {
+        self.settings.push(format!("generator {}", generator.as_ref().to_string_lossy()));
+
         if let Some(config) = self.cmake_config.as_mut() {
             config.generator(generator);
         }
@@ -173,6 +614,8 @@ impl CMakeBuilder {
     ///
     /// If unset, will use the default toolset of the selected generator.
     pub fn generator_toolset<T: AsRef<OsStr>>(&mut self, toolset_name: T) -> &mut CMakeBuilder {
+        self.settings.push(format!("generator_toolset {}", toolset_name.as_ref().to_string_lossy()));
+
         if let Some(config) = self.cmake_config.as_mut() {
             config.generator_toolset(toolset_name);
         }
@@ -183,6 +626,8 @@ impl CMakeBuilder {
     /// Adds a custom flag to pass down to the C compiler, supplementing those
     /// that this library already passes.
     pub fn cflag<P: AsRef<OsStr>>(&mut self, flag: P) -> &mut CMakeBuilder {
+        self.settings.push(format!("cflag {}", flag.as_ref().to_string_lossy()));
+
         if let Some(config) = self.cmake_config.as_mut() {
             config.cflag(flag);
         }
@@ -193,6 +638,8 @@ impl CMakeBuilder {
     /// Adds a custom flag to pass down to the C++ compiler, supplementing those
     /// that this library already passes.
     pub fn cxxflag<P: AsRef<OsStr>>(&mut self, flag: P) -> &mut CMakeBuilder {
+        self.settings.push(format!("cxxflag {}", flag.as_ref().to_string_lossy()));
+
         if let Some(config) = self.cmake_config.as_mut() {
             config.cxxflag(flag);
         }
@@ -203,6 +650,8 @@ impl CMakeBuilder {
     /// Adds a custom flag to pass down to the ASM compiler, supplementing those
     /// that this library already passes.
     pub fn asmflag<P: AsRef<OsStr>>(&mut self, flag: P) -> &mut CMakeBuilder {
+        self.settings.push(format!("asmflag {}", flag.as_ref().to_string_lossy()));
+
         if let Some(config) = self.cmake_config.as_mut() {
             config.asmflag(flag);
         }
@@ -216,6 +665,8 @@ impl CMakeBuilder {
             K: AsRef<OsStr>,
             V: AsRef<OsStr>,
     {
+        self.settings.push(format!("define {}={}", k.as_ref().to_string_lossy(), v.as_ref().to_string_lossy()));
+
         if let Some(config) = self.cmake_config.as_mut() {
             config.define(k, v);
         }
@@ -229,6 +680,8 @@ impl CMakeBuilder {
     /// This registration will modify the `CMAKE_PREFIX_PATH` environment
     /// variable for the build system generation step.
     pub fn register_dep(&mut self, dep: &str) -> &mut CMakeBuilder {
+        self.settings.push(format!("register_dep {}", dep));
+
         if let Some(config) = self.cmake_config.as_mut() {
             config.register_dep(dep);
         }
@@ -241,6 +694,8 @@ impl CMakeBuilder {
     /// This is automatically scraped from `$TARGET` which is set for Cargo
     /// build scripts so it's not necessary to call this from a build script.
     pub fn target(&mut self, target: &str) -> &mut CMakeBuilder {
+        self.settings.push(format!("target {}", target));
+
         if let Some(config) = self.cmake_config.as_mut() {
             config.target(target);
         }
@@ -253,6 +708,8 @@ impl CMakeBuilder {
     /// This is automatically scraped from `$HOST` which is set for Cargo
     /// build scripts so it's not necessary to call this from a build script.
     pub fn host(&mut self, host: &str) -> &mut CMakeBuilder {
+        self.settings.push(format!("host {}", host));
+
         if let Some(config) = self.cmake_config.as_mut() {
             config.host(host);
         }
@@ -271,6 +728,8 @@ impl CMakeBuilder {
     ///   * otherwise `CMAKE_BUILD_TYPE=RelWithDebInfo`
     /// * if `opt-level={s,z}` then `CMAKE_BUILD_TYPE=MinSizeRel`
     pub fn profile(&mut self, profile: &str) -> &mut CMakeBuilder {
+        self.settings.push(format!("profile {}", profile));
+
         if let Some(config) = self.cmake_config.as_mut() {
             config.profile(profile);
         }
@@ -282,6 +741,8 @@ impl CMakeBuilder {
     ///
     /// This option defaults to `false`, and affect only msvc targets.
     pub fn static_crt(&mut self, static_crt: bool) -> &mut CMakeBuilder {
+        self.settings.push(format!("static_crt {}", static_crt));
+
         if let Some(config) = self.cmake_config.as_mut() {
             config.static_crt(static_crt);
         }
@@ -291,6 +752,8 @@ impl CMakeBuilder {
 
     /// Add an argument to the `cmake` configure step
     pub fn configure_arg<A: AsRef<OsStr>>(&mut self, arg: A) -> &mut CMakeBuilder {
+        self.settings.push(format!("configure_arg {}", arg.as_ref().to_string_lossy()));
+
         if let Some(config) = self.cmake_config.as_mut() {
             config.configure_arg(arg);
         }
@@ -300,6 +763,8 @@ impl CMakeBuilder {
 
     /// Add an argument to the final `cmake` build step
     pub fn build_arg<A: AsRef<OsStr>>(&mut self, arg: A) -> &mut CMakeBuilder {
+        self.settings.push(format!("build_arg {}", arg.as_ref().to_string_lossy()));
+
         if let Some(config) = self.cmake_config.as_mut() {
             config.build_arg(arg);
         }
@@ -314,6 +779,8 @@ impl CMakeBuilder {
             K: AsRef<OsStr>,
             V: AsRef<OsStr>,
     {
+        self.settings.push(format!("env {}={}", key.as_ref().to_string_lossy(), value.as_ref().to_string_lossy()));
+
         if let Some(config) = self.cmake_config.as_mut() {
             config.env(key, value);
         }
@@ -352,60 +819,534 @@ impl CMakeBuilder {
         self
     }
 
+    /// Sets the directory the project is configured, built and installed in, relative paths are
+    /// relative to the package being built.
+    ///
+    /// Defaults to a directory in `OUT_DIR`. Setting it also opts out of the shared cache of
+    /// `BIND_BUILDER_CACHE_DIR`.
+    pub fn out_directory(
+        &mut self,
+        path: &Path,
+    ) -> &mut CMakeBuilder {
+        let configure_directory = resolve_path(path);
+
+        self.install_directory = configure_directory.join("install");
+        self.custom_out_directory = true;
+
+        if let Some(config) = self.cmake_config.as_mut() {
+            config.out_dir(configure_directory);
+        }
+
+        self
+    }
+
+    /// Ignore paths in the source directory when registering the sources of the project with
+    /// cargo, such as `docs` or `**/*.md`.
+    ///
+    /// Changes to any other file of a local project rerun the build script. `*` matches anything
+    /// except `/`, `**` matches any number of directories and patterns without a `/` match at any
+    /// depth. `.git` and the build directories of this crate are always ignored.
+    pub fn rerun_ignore(
+        &mut self,
+        pattern: &str,
+    ) -> &mut CMakeBuilder {
+        self.rerun_ignores.push(pattern.to_string());
+        self
+    }
+
+    /// Apply a patch to the sources before configuring, relative paths are relative to the package
+    /// being built.
+    ///
+    /// Patches are unified diffs or patches made with `git format-patch`, applied in the order they
+    /// are added with `git apply`, or without git with the `gix` feature. Changing or removing a
+    /// patch restores the original sources, cloned sources are reset and downloaded ones are
+    /// extracted again, before the patches are applied.
+    ///
+    /// Only projects that are cloned or downloaded can be patched, building fails for projects
+    /// created with `from` rather than changing sources that this crate does not own.
+    pub fn patch(
+        &mut self,
+        path: &Path,
+    ) -> &mut CMakeBuilder {
+        self.patches.push(resolve_path(path));
+        self
+    }
+
+    /// Apply every `.patch` and `.diff` file of a directory, in the order of their file names. A
+    /// series made with `git format-patch` is applied in order, see `patch`.
+    pub fn patches(
+        &mut self,
+        directory: &Path,
+    ) -> &mut CMakeBuilder {
+        self.patch(directory)
+    }
+
     /// Run this configuration, compiling the library with all the configured
     /// options.
     ///
     /// This will run both the build system generator command and the
     /// command to build the library.
+    ///
+    /// Panics if the project could not be built or installed, see `try_build`.
     pub fn build(&mut self) -> CMakeBuilder {
+        unwrap_or_panic(self.try_build())
+    }
+
+    /// Run this configuration, returning an error if the project could not be built or
+    /// installed.
+    pub fn try_build(&mut self) -> Result<CMakeBuilder, Error> {
+
+        for variable in hashed_env_variables()? {
+            rerun_if_env_changed(variable.as_str());
+        }
+
+        // Patch before collecting the sources, so that the fingerprint is of the patched files.
+        if self.cmake_config.is_some() {
+            match self.project_directory.clone() {
+                Some(project_directory) => {
+                    // Directories are watched as well, so that adding or removing a patch builds again.
+                    for path in self.patches.iter() {
+                        rerun_if_changed(path.as_path())?;
+                    }
+
+                    let archive = self.archive_path.as_deref().zip(self.archive_stamp_path.as_deref());
+
+                    for hash in patch_sources(project_directory.as_path(), self.collect_patches()?.as_slice(), archive)? {
+                        // Changing a patch changes the result of building.
+                        self.settings.push(format!("patch {}", hash));
+                    }
+                },
+                None if !self.patches.is_empty() => return Err(Error::PatchFailed(format!(
+                    "{} is not cloned or downloaded, patch a copy of its sources instead", self.name
+                ))),
+                None => {},
+            }
+        }
+
+        let mut sources = Vec::new();
+        let mut watched = Vec::new();
 
-        let build_directory = match self.cmake_config.as_mut() {
+        if let Some(source_directory) = &self.source_directory {
+            collect_sources(source_directory, source_directory, self.rerun_ignores.as_slice(), &mut sources, &mut watched)?;
+        }
+
+        for path in watched.iter() {
+            rerun_if_changed(path.as_path())?;
+        }
+
+        // Held until the end of the build.
+        let _cache_lock = match self.cmake_config.is_some() {
+            true => self.use_cache()?,
+            false => None,
+        };
+
+        let configure_directory = self.install_directory.parent().unwrap().to_path_buf();
+        let stamp_path = configure_directory.join(STAMP_FILE_NAME);
+
+        let fingerprint = match self.cmake_config.is_some() {
+            true => Some(self.fingerprint(sources.as_slice())?),
+            false => None,
+        };
+
+        if let (Some(fingerprint), Some(config)) = (&fingerprint, self.cmake_config.as_ref()) {
+            let stamp = fs::read_to_string(stamp_path.as_path()).unwrap_or_default();
+
+            // Nothing changed since the last build, reuse it without running cmake. Projects
+            // without install rules have no install tree and are used from the build directory.
+            let build_directory = configure_directory.join("build");
+
+            if stamp.trim() == fingerprint && build_directory.is_dir() {
+                let build_info = self.read_build_info(build_directory.as_path(), config.get_profile());
+
+                return Ok(self.built(build_directory, build_info));
+            }
+
+            // Remove the stamp so that a failed build is not mistaken for a successful one.
+            if stamp_path.exists() {
+                fs::remove_file(stamp_path.as_path())?;
+            }
+        }
+
+        let install_prefix = utf8_path(self.install_directory.as_path())?.to_string();
+
+        let (build_directory, build_type) = match self.cmake_config.as_mut() {
             Some(config) => {
+                let build_directory = configure_directory.join("build");
+
+                // A cache entry may have been configured by another workspace, with the sources
+                // somewhere else. CMake refuses to configure it again, so start over.
+                let configured = configured_source_directory(build_directory.as_path());
+
+                if let (Some(source_directory), Some(configured)) = (&self.source_directory, configured) {
+                    if !is_same_directory(source_directory, configured.as_path()) {
+                        fs::remove_file(build_directory.join("CMakeCache.txt"))?;
+
+                        if build_directory.join("CMakeFiles").is_dir() {
+                            fs::remove_dir_all(build_directory.join("CMakeFiles"))?;
+                        }
+                    }
+                }
+
+                // Query the CMake File API so that targets can be discovered after building.
+                file_api::write_query(build_directory.as_path())?;
+
                 config.build_target(
                     self.build_target.clone().unwrap_or("all".to_string()).as_str()
                 )
                     // We also need to set CMAKE_INSTALL_PREFIX while building otherwise the
                     // cmake crate will default and override with an incorrect path.
-                    .define("CMAKE_INSTALL_PREFIX", self.install_directory.clone().to_str().unwrap())
+                    .define("CMAKE_INSTALL_PREFIX", install_prefix.as_str());
 
-                    .build()
-                    .join("build")
+                // The cmake crate panics when configuring or building fails, catch it so that
+                // it can be reported as an error instead.
+                let build_directory = panic::catch_unwind(AssertUnwindSafe(|| config.build()))
+                    .map_err(|e| Error::CMakeConfigureFailed(panic_message(e)))?
+                    .join("build");
+
+                (build_directory, config.get_profile().to_string())
             },
             None => {
-                self.build_directory.clone()
-                    .expect("Could not find build directory argument, is it set?")
+                let build_directory = self.build_directory.clone()
+                    .ok_or(Error::CMakeConfigureFailed(
+                        "could not find build directory argument, is it set?".to_string()
+                    ))?;
+
+                file_api::write_query(build_directory.as_path())?;
+
+                let build_type = match get_profile()?.as_str() {
+                    "debug" => "Debug",
+                    _ => "Release",
+                };
+
+                (build_directory, build_type.to_string())
             }
         };
 
-        Command::new(cmake_executable())
+        // Configuring is skipped when the project has been configured before, or the directory
+        // was configured outside this crate. Re-run it so CMake answers our query.
+        if !file_api::has_reply(build_directory.as_path()) {
+            run(Command::new(cmake_executable())
+                .arg(".")
+                .current_dir(build_directory.clone())
+            ).map_err(|e| Error::CMakeConfigureFailed(e.to_string()))?;
+        }
+
+        let build_info = self.read_build_info(build_directory.as_path(), build_type.as_str());
+
+        run(Command::new(cmake_executable())
             // Actual install command
             .arg("--install")
             .arg(".")
 
             .arg("--prefix")
-            .arg(self.install_directory.clone().to_str().unwrap())
+            .arg(install_prefix.as_str())
 
             .current_dir(build_directory.clone())
-            .status()
-            .expect("Could not install repo, is cmake installed?");
+        ).map_err(Error::InstallFailed)?;
 
-        // Make a new object. Since we can't clone/copy cmake::Config :(
-        let name = self.name.clone();
-        let install_directory = self.install_directory.clone();
-        let build_target = self.build_target.clone();
+        if let Some(fingerprint) = fingerprint {
+            fs::write(stamp_path, fingerprint)?;
+        }
+
+        Ok(self.built(build_directory, build_info))
+    }
+
+    /// Patch files to apply, with directories expanded to the patches in them.
+    fn collect_patches(&self) -> Result<Vec<PathBuf>, Error> {
+        let mut patches = Vec::new();
+
+        for path in self.patches.iter() {
+            if !path.is_dir() {
+                patches.push(path.clone());
+                continue;
+            }
+
+            let mut entries = fs::read_dir(path)?
+                .filter_map(|x| x.ok())
+                .map(|x| x.path())
+                .filter(|x| x.is_file())
+                .filter(|x| matches!(x.extension().and_then(|x| x.to_str()), Some("patch" | "diff")))
+                .collect::<Vec<PathBuf>>();
 
+            entries.sort();
+            patches.append(&mut entries);
+        }
+
+        Ok(patches)
+    }
+
+    /// Build in the shared cache when `BIND_BUILDER_CACHE_DIR` is set and the project was cloned
+    /// from git or downloaded, so other workspaces building the same revision can reuse the
+    /// install tree.
+    ///
+    /// Entries are keyed by URL, revision, target, profile and configuration. The returned lock
+    /// must be held until the build is finished, concurrent builds of the same entry wait for it.
+    fn use_cache(&mut self) -> Result<Option<File>, Error> {
+        let (Some(cache_directory), Some(url), Some(revision)) = (cache_directory(), &self.url, &self.revision) else {
+            return Ok(None);
+        };
+
+        if self.custom_out_directory {
+            return Ok(None);
+        }
+
+        let key = Sha256::new()
+            .update_str(url.as_str())
+            .update_str(revision.as_str())
+            .update_str(target()?.as_str())
+            .update_str(get_profile()?.as_str())
+            .update_str(self.configuration_hash()?.as_str())
+            .finish();
+
+        let entry_name = format!("{}-{}", self.name, &key[..16]);
+        fs::create_dir_all(cache_directory.as_path())?;
+
+        let lock = File::create(cache_directory.join(format!("{}.lock", entry_name)))?;
+
+        match lock.try_lock() {
+            Ok(()) => {},
+            Err(TryLockError::WouldBlock) => {
+                print_warning(format!("Waited for another build of {} in {:?}", self.name, cache_directory));
+                lock.lock()?;
+            },
+            Err(TryLockError::Error(e)) => return Err(e.into()),
+        }
+
+        let configure_directory = cache_directory.join(entry_name);
+        self.install_directory = configure_directory.join("install");
+
+        if let Some(config) = self.cmake_config.as_mut() {
+            config.out_dir(configure_directory);
+        }
+
+        Ok(Some(lock))
+    }
+
+    /// Read the targets of the project, warning instead of failing since older versions of CMake
+    /// do not support the File API and the install tree is still usable.
+    fn read_build_info(
+        &self,
+        build_directory: &Path,
+        build_type: &str,
+    ) -> Option<BuildInfo> {
+        match file_api::read_build_info(build_directory, build_type) {
+            Ok(build_info) => Some(build_info),
+            Err(e) => {
+                print_warning(format!("Could not read targets of {}: {}", self.name, e));
+                None
+            }
+        }
+    }
+
+    /// Hash of the settings made through this builder and the toolchain, which is everything that
+    /// changes the result of building apart from the sources and where it is installed.
+    fn configuration_hash(&self) -> Result<String, Error> {
+        let mut hash = Sha256::new();
+
+        hash.update_str(env!("CARGO_PKG_VERSION"));
+        hash.update_str(self.build_target.as_deref().unwrap_or("all"));
+
+        for setting in self.settings.iter() {
+            hash.update_str(setting.as_str());
+        }
+
+        for variable in hashed_env_variables()? {
+            if let Ok(value) = env::var(variable.as_str()) {
+                hash.update_str(format!("{}={}", variable, value).as_str());
+            }
+        }
+
+        Ok(hash.finish())
+    }
+
+    /// Fingerprint of everything that changes the result of building the project: the settings
+    /// made through this builder, the toolchain, the git revision and the source files.
+    ///
+    /// The stamp it is compared to is kept in the configure directory, next to the build directory
+    /// a skipped build is used from.
+    fn fingerprint(
+        &self,
+        sources: &[PathBuf],
+    ) -> Result<String, Error> {
+        let mut hash = Sha256::new();
+
+        hash.update_str(self.configuration_hash()?.as_str());
+
+        if let Some(revision) = &self.revision {
+            hash.update_str(revision.as_str());
+        }
+
+        // Sources are compared by size and modification time, reading every file of a large
+        // project would take longer than letting CMake check it.
+        for source in sources.iter() {
+            let metadata = fs::metadata(source)?;
+            let modified = metadata.modified()?
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default();
+
+            hash.update_str(source.to_string_lossy().as_ref());
+            hash.update_str(format!("{} {}", metadata.len(), modified.as_nanos()).as_str());
+        }
+
+        Ok(hash.finish())
+    }
+
+    /// Make a new object. Since we can't clone/copy cmake::Config :(
+    fn built(
+        &self,
+        build_directory: PathBuf,
+        build_info: Option<BuildInfo>,
+    ) -> CMakeBuilder {
         CMakeBuilder {
-            name,
+            name: self.name.clone(),
             cmake_config: None,
-            build_directory: Some(build_directory.clone()),
-            install_directory,
-            build_target
+            build_directory: Some(build_directory),
+            install_directory: self.install_directory.clone(),
+            custom_out_directory: self.custom_out_directory,
+            build_target: self.build_target.clone(),
+            build_info,
+
+            source_directory: self.source_directory.clone(),
+            rerun_ignores: self.rerun_ignores.clone(),
+
+            project_directory: self.project_directory.clone(),
+            archive_path: self.archive_path.clone(),
+            archive_stamp_path: self.archive_stamp_path.clone(),
+            patches: self.patches.clone(),
+
+            url: self.url.clone(),
+            revision: self.revision.clone(),
+            settings: self.settings.clone(),
         }
     }
 
-    pub (crate) fn get_install_directory(&self) -> &PathBuf {
+    /// Directory the project is installed into.
+    pub fn get_install_directory(&self) -> &PathBuf {
         &self.install_directory
     }
 
+    /// Full SHA of the commit a project cloned from git was built from, or the SHA-256 of the
+    /// archive a project was downloaded as. `None` for local projects.
+    pub fn get_revision(&self) -> Option<&str> {
+        self.revision.as_deref()
+    }
+
+    /// Targets and artifacts discovered through the CMake File API once the project is built.
+    ///
+    /// This is `None` before building, or when the version of CMake used does not support the
+    /// File API (3.14 or newer is required).
+    pub fn get_build_info(&self) -> Option<&BuildInfo> {
+        self.build_info.as_ref()
+    }
+
     pub (crate) fn get_build_target(&self) -> &Option<String> { &self.build_target }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_directory::TestDirectory;
+
+    #[test]
+    fn detects_commit_hashes() {
+        assert!(is_commit_hash("47523026807efe01473ead8a3d5bb00278ade0e3"));
+        assert!(is_commit_hash("47523026807EFE01473EAD8A3D5BB00278ADE0E3"));
+        assert!(is_commit_hash(&"a".repeat(64)));
+
+        assert!(!is_commit_hash("v1.3.1"));
+        assert!(!is_commit_hash("4752302"));
+        assert!(!is_commit_hash("47523026807efe01473ead8a3d5bb00278ade0eg"));
+    }
+
+    #[test]
+    fn rejects_abbreviated_revisions() {
+        let result = CMakeBuilder::try_clone_pinned("foo", "https://example.com/foo.git", "v1", "4752302");
+
+        assert!(matches!(result, Err(Error::InvalidRevision(revision)) if revision == "4752302"));
+    }
+
+    #[test]
+    fn restores_sources_when_patches_change() {
+        let directory = TestDirectory::new("cmake-builder-patches");
+        directory.write("archive/foo-1.0/foo.txt", "one\n");
+
+        run(Command::new("tar")
+            .args(["-czf", "foo.tar.gz", "-C", "archive", "foo-1.0"])
+            .current_dir(directory.as_path())
+        ).unwrap();
+
+        let archive_path = directory.join("foo.tar.gz");
+        let project_directory = directory.join("foo");
+        let stamp_path = directory.write("foo.sha256", "1234");
+        let archive = Some((archive_path.as_path(), stamp_path.as_path()));
+
+        download::extract(archive_path.as_path(), project_directory.as_path()).unwrap();
+
+        let patch = |to: &str| directory.write("foo.patch", format!("--- a/foo.txt\n+++ b/foo.txt\n@@ -1 +1 @@\n-one\n+{}\n", to));
+        let read = || fs::read_to_string(project_directory.join("foo.txt")).unwrap();
+        let stamp = || fs::read_to_string(stamp_path.as_path()).unwrap();
+
+        let patches = [patch("two")];
+        let hashes = patch_sources(project_directory.as_path(), &patches, archive).unwrap();
+
+        assert_eq!(read(), "two\n");
+        assert_eq!(stamp(), format!("1234\n{}", hashes[0]));
+
+        patch_sources(project_directory.as_path(), &patches, archive).unwrap();
+        assert_eq!(read(), "two\n");
+
+        assert!(patch_sources(project_directory.as_path(), &[], archive).unwrap().is_empty());
+        assert_eq!(read(), "one\n");
+        assert_eq!(stamp(), "1234");
+
+        patch("three");
+        patch_sources(project_directory.as_path(), &patches, archive).unwrap();
+        assert_eq!(read(), "three\n");
+
+        patch("two");
+        patch_sources(project_directory.as_path(), &patches, archive).unwrap();
+        assert_eq!(read(), "two\n");
+    }
+
+    #[test]
+    fn reads_configured_source_directories() {
+        let directory = TestDirectory::new("cmake-builder-cache");
+        let source_directory = directory.join("source");
+
+        fs::create_dir(source_directory.as_path()).unwrap();
+        assert_eq!(configured_source_directory(directory.as_path()), None);
+
+        directory.write("CMakeCache.txt", format!(
+            "# This is the CMakeCache file.\nCMAKE_BUILD_TYPE:STRING=Release\nCMAKE_HOME_DIRECTORY:INTERNAL={}\n",
+            source_directory.display(),
+        ));
+
+        let configured = configured_source_directory(directory.as_path()).unwrap();
+
+        assert!(is_same_directory(configured.as_path(), source_directory.as_path()));
+        assert!(!is_same_directory(configured.as_path(), directory.as_path()));
+        assert!(!is_same_directory(configured.as_path(), directory.join("missing").as_path()));
+    }
+
+    #[test]
+    fn watches_directories_without_ignored_paths() {
+        let directory = TestDirectory::new("cmake-builder-sources");
+
+        for name in ["CMakeLists.txt", "README.md", "docs/index.html", "include/foo/foo.h", "src/foo.c", "src/notes.md"] {
+            directory.write(name, "");
+        }
+
+        let ignores = ["docs".to_string(), "**/*.md".to_string()];
+        let mut sources = Vec::new();
+        let mut watched = Vec::new();
+
+        assert!(!collect_sources(directory.as_path(), directory.as_path(), &ignores, &mut sources, &mut watched).unwrap());
+
+        let relative = |paths: &[PathBuf]| paths.iter()
+            .map(|x| x.strip_prefix(directory.as_path()).unwrap().to_string_lossy().replace('\\', "/"))
+            .collect::<Vec<String>>();
+
+        assert_eq!(relative(&sources), ["CMakeLists.txt", "include/foo/foo.h", "src/foo.c"]);
+        assert_eq!(relative(&watched), ["CMakeLists.txt", "include", "src/foo.c"]);
+    }
+}
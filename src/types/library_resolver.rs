@@ -0,0 +1,287 @@
+use std::env;
+use std::path::{Path, PathBuf};
+use crate::commands::rerun_if_env_changed;
+use crate::error::{unwrap_or_panic, Error};
+use crate::resolve::resolve_link_target;
+use crate::types::link::{LinkKind, LinkModifiers, LinkTarget};
+use crate::types::local_library::LocalLibrary;
+use crate::version::VersionReq;
+
+enum Source {
+    Environment(String),
+    PkgConfig(String),
+    Prebuilt(PathBuf),
+    Vendored(Box<dyn FnOnce() -> Result<LocalLibrary, Error>>),
+}
+
+impl Source {
+
+    fn describe(&self) -> String {
+        match self {
+            Source::Environment(variable) => format!("environment variable {}", variable),
+            Source::PkgConfig(package) => format!("pkg-config package {}", package),
+            Source::Prebuilt(path) => format!("prebuilt directory {:?}", path),
+            Source::Vendored(_) => "vendored build".to_string(),
+        }
+    }
+}
+
+/// Resolves a `LocalLibrary` by trying a list of sources in order.
+///
+/// The first source that produces a library satisfying the version requirement is used. This
+/// makes it possible to prefer a system library and fall back to building a vendored copy.
+pub struct LibraryResolver {
+    name: String,
+    version_req: String,
+
+    sources: Vec<Source>,
+
+    link_targets: Vec<LinkTarget>,
+    system_link_targets: Vec<String>,
+}
+
+impl LibraryResolver {
+
+    /// Create a new `LibraryResolver` for a library, the name is only used for error messages.
+    pub fn new(name: &str) -> LibraryResolver {
+        LibraryResolver {
+            name: name.to_string(),
+            version_req: "*".to_string(),
+
+            sources: Vec::new(),
+
+            link_targets: Vec::new(),
+            system_link_targets: Vec::new(),
+        }
+    }
+
+    /// Set the version requirement that the resolved library must satisfy, such as `>= 1.2, < 2`.
+    ///
+    /// Libraries without a known version are assumed to satisfy the requirement, use
+    /// `LocalLibrary::version` to set the version of prebuilt or vendored libraries.
+    pub fn version(
+        &mut self,
+        version_req: &str,
+    ) -> &mut LibraryResolver {
+        self.version_req = version_req.to_string();
+        self
+    }
+
+    /// Try an install directory given by an environment variable.
+    ///
+    /// This source is skipped when the variable is not set or the directory does not contain every
+    /// link target.
+    pub fn env_override(
+        &mut self,
+        variable: &str,
+    ) -> &mut LibraryResolver {
+        self.sources.push(Source::Environment(variable.to_string()));
+        self
+    }
+
+    /// Try a pkg-config package, see `LocalLibrary::from_pkg_config`.
+    pub fn pkg_config(
+        &mut self,
+        package: &str,
+    ) -> &mut LibraryResolver {
+        self.sources.push(Source::PkgConfig(package.to_string()));
+        self
+    }
+
+    /// Try a prebuilt install directory, see `LocalLibrary::new`.
+    ///
+    /// This source is skipped when the directory does not contain every link target.
+    pub fn prebuilt(
+        &mut self,
+        install_directory: &Path,
+    ) -> &mut LibraryResolver {
+        self.sources.push(Source::Prebuilt(install_directory.to_path_buf()));
+        self
+    }
+
+    /// Try building a vendored copy of the library, usually with a `CMakeBuilder`.
+    ///
+    /// The closure is only called if all previous sources failed.
+    pub fn vendored<F>(
+        &mut self,
+        build: F,
+    ) -> &mut LibraryResolver
+        where
+            F: FnOnce() -> Result<LocalLibrary, Error> + 'static,
+    {
+        self.sources.push(Source::Vendored(Box::new(build)));
+        self
+    }
+
+    /// Add a target to link against when the library comes from an environment variable or a
+    /// prebuilt directory.
+    ///
+    /// See `LocalLibrary::link_target`.
+    pub fn link_target(
+        &mut self,
+        target: &str,
+    ) -> &mut LibraryResolver {
+        self.link_target_with(target, LinkKind::PreferStatic)
+    }
+
+    /// Add a target to link against when the library comes from an environment variable or a
+    /// prebuilt directory, choosing how it is linked.
+    ///
+    /// See `LocalLibrary::link_target_with`.
+    pub fn link_target_with(
+        &mut self,
+        target: &str,
+        kind: LinkKind,
+    ) -> &mut LibraryResolver {
+        self.link_targets.push(LinkTarget {
+            name: target.to_string(),
+            kind,
+            modifiers: LinkModifiers::default(),
+        });
+        self
+    }
+
+    /// Add a system target to link against when the library comes from an environment variable or
+    /// a prebuilt directory.
+    ///
+    /// See `LocalLibrary::link_system_target`.
+    pub fn link_system_target(
+        &mut self,
+        target: &str,
+    ) -> &mut LibraryResolver {
+        self.system_link_targets.push(target.to_string());
+        self
+    }
+
+    /// Resolve the library from the first source that succeeds.
+    ///
+    /// Panics if no source succeeded, see `try_resolve`.
+    pub fn resolve(&mut self) -> LocalLibrary {
+        unwrap_or_panic(self.try_resolve())
+    }
+
+    /// Resolve the library from the first source that succeeds, returning an error describing
+    /// every attempt if none did.
+    ///
+    /// Sources are consumed, so a resolver can only be resolved once.
+    pub fn try_resolve(&mut self) -> Result<LocalLibrary, Error> {
+        let version_req = VersionReq::parse(self.version_req.as_str())?;
+        let mut attempts = Vec::new();
+
+        for source in std::mem::take(&mut self.sources) {
+            let description = source.describe();
+
+            let library = match source {
+                Source::Environment(variable) => {
+                    rerun_if_env_changed(variable.as_str());
+
+                    match env::var_os(variable.as_str()) {
+                        Some(path) => self.library_from_directory(Path::new(&path)),
+                        None => Err(Error::LibraryNotFound(format!("{} is not set", variable))),
+                    }
+                },
+                Source::PkgConfig(package) => {
+                    LocalLibrary::try_from_pkg_config(package.as_str(), version_req.as_str())
+                },
+                Source::Prebuilt(path) => self.library_from_directory(path.as_path()),
+                Source::Vendored(build) => build(),
+            };
+
+            let library = library.and_then(|library| match library.get_version() {
+                Some(version) if !version_req.matches(version) => Err(Error::VersionMismatch {
+                    name: self.name.clone(),
+                    version: version.clone(),
+                    required: version_req.as_str().to_string(),
+                }),
+                _ => Ok(library),
+            });
+
+            match library {
+                Ok(library) => return Ok(library),
+                Err(error) => attempts.push((description, error)),
+            }
+        }
+
+        Err(Error::ResolutionFailed {
+            name: self.name.clone(),
+            attempts,
+        })
+    }
+
+    fn library_from_directory(
+        &self,
+        install_directory: &Path,
+    ) -> Result<LocalLibrary, Error> {
+        if !install_directory.is_dir() {
+            return Err(Error::LibraryNotFound(
+                format!("install directory {:?} does not exist", install_directory)
+            ));
+        }
+
+        let mut library = LocalLibrary::new(install_directory);
+
+        for target in self.link_targets.iter() {
+            library.link_target_with(target.name.as_str(), target.kind);
+        }
+
+        for target in self.system_link_targets.iter() {
+            library.link_system_target(target);
+        }
+
+        // Fall back to the next source now rather than failing to link once the library is bound.
+        let mut library_directories = library.get_library_directories().clone();
+        library_directories.retain(|x| x.is_dir());
+
+        let mut runtime_directories = library.get_runtime_directories().clone();
+        runtime_directories.retain(|x| x.is_dir());
+
+        for target in library.get_link_targets() {
+            resolve_link_target(target, library_directories.as_slice(), runtime_directories.as_slice())?;
+        }
+
+        Ok(library)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn missing_directory() -> PathBuf {
+        env::temp_dir().join(format!("bind-builder-resolver-missing-{}", std::process::id()))
+    }
+
+    #[test]
+    fn falls_back_to_the_next_source() {
+        let library = LibraryResolver::new("foo")
+            .version(">= 1")
+            .prebuilt(missing_directory().as_path())
+            .vendored(|| Ok(LocalLibrary::new(env::temp_dir().as_path()).version("1.2.0").get()))
+            .try_resolve()
+            .unwrap();
+
+        assert_eq!(library.get_version(), &Some("1.2.0".to_string()));
+    }
+
+    #[test]
+    fn reports_every_attempt() {
+        let error = LibraryResolver::new("foo")
+            .version(">= 2")
+            .prebuilt(missing_directory().as_path())
+            .vendored(|| Ok(LocalLibrary::new(env::temp_dir().as_path()).version("1.2.0").get()))
+            .try_resolve()
+            .err()
+            .unwrap();
+
+        let Error::ResolutionFailed { name, attempts } = error else {
+            panic!("unexpected error: {}", error);
+        };
+
+        assert_eq!(name, "foo");
+        assert_eq!(attempts.len(), 2);
+        assert!(attempts[0].0.starts_with("prebuilt directory"));
+        assert!(matches!(attempts[0].1, Error::LibraryNotFound(_)));
+        assert_eq!(attempts[1].0, "vendored build");
+        assert!(matches!(attempts[1].1, Error::VersionMismatch { .. }));
+    }
+}
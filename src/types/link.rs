@@ -0,0 +1,183 @@
+use std::path::Path;
+use crate::error::Error;
+use crate::variables::{platform, uses_elf_loader, Platform};
+
+/// How a link target should be linked.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum LinkKind {
+    /// Link a static library if there is one, otherwise a shared library.
+    #[default]
+    PreferStatic,
+
+    /// Link a shared library if there is one, otherwise a static library.
+    PreferDynamic,
+
+    /// Only link a static library.
+    Static,
+
+    /// Only link a static library, including every object in it rather than only the ones that
+    /// are referenced. This is required for libraries that register themselves through static
+    /// constructors.
+    StaticWholeArchive,
+
+    /// Only link a shared library.
+    Dynamic,
+
+    /// Link a macOS framework, either from a library directory or from the system.
+    Framework,
+}
+
+/// A target to link against along with how it should be linked.
+#[derive(Clone, Debug, PartialEq)]
+pub (crate) struct LinkTarget {
+    pub (crate) name: String,
+    pub (crate) kind: LinkKind,
+    pub (crate) modifiers: LinkModifiers,
+}
+
+/// Modifiers passed to rustc along with a link directive.
+///
+/// Modifiers that are not set use the rustc default, modifiers that do not apply to the type of
+/// library being linked are left out. See the `-l` option of rustc for what each modifier does.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct LinkModifiers {
+    pub (crate) whole_archive: Option<bool>,
+    pub (crate) bundle: Option<bool>,
+    pub (crate) verbatim: Option<bool>,
+    pub (crate) as_needed: Option<bool>,
+}
+
+impl LinkModifiers {
+
+    /// Create a new `LinkModifiers` instance with every modifier left to the rustc default.
+    pub fn new() -> LinkModifiers {
+        LinkModifiers::default()
+    }
+
+    /// Include every object of a static library rather than only the ones that are referenced.
+    pub fn whole_archive(
+        &mut self,
+        enabled: bool,
+    ) -> &mut LinkModifiers {
+        self.whole_archive = Some(enabled);
+        self
+    }
+
+    /// Whether a static library is packed into the rlib of the crate.
+    ///
+    /// With `bundle(false)` the library is linked when the final binary is linked instead, which
+    /// avoids duplicate symbols when several crates link the same archive.
+    pub fn bundle(
+        &mut self,
+        enabled: bool,
+    ) -> &mut LinkModifiers {
+        self.bundle = Some(enabled);
+        self
+    }
+
+    /// Pass the link target to the linker as a file name, without adding a prefix or extension.
+    ///
+    /// This allows linking files such as `libfoo.so.3`, the link target is then the full file
+    /// name.
+    pub fn verbatim(
+        &mut self,
+        enabled: bool,
+    ) -> &mut LinkModifiers {
+        self.verbatim = Some(enabled);
+        self
+    }
+
+    /// Only keep a shared library or framework if it is actually used.
+    pub fn as_needed(
+        &mut self,
+        enabled: bool,
+    ) -> &mut LinkModifiers {
+        self.as_needed = Some(enabled);
+        self
+    }
+
+    /// Finalize the `LinkModifiers` configuration.
+    pub fn get(&self) -> LinkModifiers {
+        *self
+    }
+
+    /// Modifiers set here, falling back to `defaults` for the ones that are not.
+    pub (crate) fn or(&self, defaults: &LinkModifiers) -> LinkModifiers {
+        LinkModifiers {
+            whole_archive: self.whole_archive.or(defaults.whole_archive),
+            bundle: self.bundle.or(defaults.bundle),
+            verbatim: self.verbatim.or(defaults.verbatim),
+            as_needed: self.as_needed.or(defaults.as_needed),
+        }
+    }
+
+    pub (crate) fn is_verbatim(&self) -> bool {
+        self.verbatim.unwrap_or(false)
+    }
+}
+
+/// Runtime search path added for shared libraries that are linked, so they can be found without
+/// setting `LD_LIBRARY_PATH` or `DYLD_LIBRARY_PATH`.
+///
+/// Windows has no runtime search path, DLLs are found next to the executable. Other targets only
+/// get one on Apple platforms and on operating systems with an ELF dynamic loader such as Linux,
+/// Android and the BSDs, not on WebAssembly or bare metal targets.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum RPath {
+    /// The directory of the executable, where shared libraries are copied to. This is `$ORIGIN`
+    /// on Unix and `@loader_path` on Apple targets.
+    #[default]
+    Origin,
+
+    /// The `lib` directory next to the directory of the executable, for installs that follow the
+    /// `bin` and `lib` layout.
+    OriginLib,
+
+    /// The absolute path of the directory the library was found in, which is useful for
+    /// development builds but does not work once the executable is moved to another machine.
+    LibraryDirectory,
+
+    /// Do not add a runtime search path.
+    Disabled,
+}
+
+impl RPath {
+
+    /// Runtime search path for a shared library found at `library_path`, `None` if there is none.
+    pub (crate) fn get_entry(
+        &self,
+        library_path: &Path,
+    ) -> Result<Option<String>, Error> {
+        let origin = match platform()? {
+            Platform::Windows => return Ok(None),
+            Platform::Apple => "@loader_path",
+            Platform::Unix if uses_elf_loader()? => "$ORIGIN",
+            Platform::Unix => return Ok(None),
+        };
+
+        Ok(match self {
+            RPath::Origin => Some(origin.to_string()),
+            RPath::OriginLib => Some(format!("{}/../lib", origin)),
+            RPath::LibraryDirectory => library_path.parent()
+                .map(|x| x.to_string_lossy().to_string()),
+            RPath::Disabled => None,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn falls_back_to_default_modifiers() {
+        let defaults = LinkModifiers::new().bundle(false).verbatim(true).get();
+        let modifiers = LinkModifiers::new().whole_archive(true).verbatim(false).get().or(&defaults);
+
+        assert_eq!(modifiers.whole_archive, Some(true));
+        assert_eq!(modifiers.bundle, Some(false));
+        assert_eq!(modifiers.verbatim, Some(false));
+        assert_eq!(modifiers.as_needed, None);
+        assert!(!modifiers.is_verbatim());
+    }
+}
@@ -1,11 +1,24 @@
+use std::env;
 use std::path::{Path, PathBuf};
+use crate::cmake_package;
+use crate::commands::rerun_if_env_changed;
+use crate::error::{unwrap_or_panic, Error};
+use crate::get_library_name;
+use crate::pkg_config;
+use crate::types::build_info::{BuildInfo, BuildTarget};
 use crate::types::cmake_builder::CMakeBuilder;
+use crate::types::link::{LinkKind, LinkModifiers, LinkTarget, RPath};
+use crate::version::VersionReq;
 
 const DEFAULT_LIBRARY_DIRECTORIES: [&str; 2] = [
     "lib",
     "lib64",
 ];
 
+const DEFAULT_RUNTIME_DIRECTORIES: [&str; 1] = [
+    "bin",
+];
+
 const DEFAULT_INCLUDE_DIRECTORIES: [&str; 1] = [
     "include",
 ];
@@ -16,12 +29,26 @@ const DEFAULT_INCLUDE_DIRECTORIES: [&str; 1] = [
 #[derive(Clone)]
 pub struct LocalLibrary {
     install_directory: PathBuf,
+    version: Option<String>,
+    strict: bool,
+    prebuilt: bool,
 
-    link_targets: Vec<String>,
+    rpath: RPath,
+    link_modifiers: LinkModifiers,
+    link_targets: Vec<LinkTarget>,
     system_link_targets: Vec<String>,
 
     include_directories: Vec<PathBuf>,
     library_directories: Vec<PathBuf>,
+    runtime_directories: Vec<PathBuf>,
+
+    dependencies: Vec<LocalLibrary>,
+}
+
+/// Libraries in the order they should be linked, along with any dependency cycles between them.
+pub (crate) struct LinkOrder<'a> {
+    pub (crate) libraries: Vec<&'a LocalLibrary>,
+    pub (crate) cycles: Vec<Vec<&'a LocalLibrary>>,
 }
 
 impl LocalLibrary {
@@ -31,15 +58,8 @@ impl LocalLibrary {
     /// This is useful when you want to ship binaries with your crate.
     pub fn new(install_directory: &Path) -> LocalLibrary {
 
-        let mut local_library = LocalLibrary {
-            install_directory: install_directory.into(),
-
-            link_targets: Vec::new(),
-            system_link_targets: Vec::new(),
-
-            include_directories: Vec::new(),
-            library_directories: Vec::new(),
-        };
+        let mut local_library = LocalLibrary::empty(install_directory);
+        local_library.prebuilt = true;
 
         // Add default include and library directories.
         for include_directory in DEFAULT_INCLUDE_DIRECTORIES {
@@ -50,27 +70,346 @@ impl LocalLibrary {
             local_library.add_library_directory(Path::new(library_directory));
         }
 
+        for runtime_directory in DEFAULT_RUNTIME_DIRECTORIES {
+            local_library.add_runtime_directory(Path::new(runtime_directory));
+        }
+
         local_library
     }
 
     /// Create a new `LocalLibrary` instance from a `CMakeBuilder`.
+    ///
+    /// Panics if the repository has not been built, see `try_from`.
     pub fn from(
         repository: CMakeBuilder,
     ) -> LocalLibrary {
+        unwrap_or_panic(LocalLibrary::try_from(repository))
+    }
 
-        let install_directory = match repository.get_install_directory().exists() {
-            true => repository.get_install_directory(),
-            false => panic!("Could not find install directory, is repository built?")
-        };
+    /// Create a new `LocalLibrary` instance from a `CMakeBuilder`, returning an error if the
+    /// repository has not been built.
+    pub fn try_from(
+        repository: CMakeBuilder,
+    ) -> Result<LocalLibrary, Error> {
 
         let build_target = repository.get_build_target().clone().unwrap_or("all".to_string());
+
+        // Projects without install rules can still be used straight from the build directory.
+        let install_directory = match (repository.get_install_directory().exists(), repository.get_build_info()) {
+            (true, _) => repository.get_install_directory(),
+            (false, Some(build_info)) => {
+                return LocalLibrary::try_from_build_info(build_info, build_target.as_str());
+            },
+            (false, None) => return Err(Error::LibraryNotFound(
+                format!("could not find install directory {:?}, is repository built?",
+                        repository.get_install_directory())
+            ))
+        };
+
         let mut local_library = LocalLibrary::new(install_directory);
 
+        // Changes are tracked through the sources of the project instead, the install tree is
+        // written while the build script runs.
+        local_library.prebuilt = false;
+
         if build_target.to_lowercase() != "all"{
             local_library.link_target(build_target.as_str());
         }
 
-        local_library
+        Ok(local_library)
+    }
+
+    /// Create a new `LocalLibrary` from the targets of a build directory.
+    ///
+    /// When `build_target` is `all`, the include and library directories of every library are
+    /// added but nothing is linked, the same as for an install directory. Otherwise the target
+    /// and the libraries it depends on are linked.
+    fn try_from_build_info(
+        build_info: &BuildInfo,
+        build_target: &str,
+    ) -> Result<LocalLibrary, Error> {
+
+        let mut local_library = LocalLibrary::empty(build_info.get_build_directory());
+
+        let targets = match build_target.to_lowercase() == "all" {
+            true => build_info.get_targets().iter().collect::<Vec<&BuildTarget>>(),
+            false => {
+                let target = build_info.get_target(build_target)
+                    .ok_or(Error::LibraryNotFound(
+                        format!("build directory {:?} has no target {}", build_info.get_build_directory(), build_target)
+                    ))?;
+
+                fn visit<'a>(
+                    build_info: &'a BuildInfo,
+                    target: &'a BuildTarget,
+                    visited: &mut Vec<&'a String>,
+                    targets: &mut Vec<&'a BuildTarget>,
+                ) {
+                    if visited.contains(&target.get_name()) {
+                        return;
+                    }
+
+                    visited.push(target.get_name());
+
+                    for dependency in target.get_dependencies() {
+                        if let Some(dependency) = build_info.get_target(dependency) {
+                            visit(build_info, dependency, visited, targets);
+                        }
+                    }
+
+                    targets.push(target);
+                }
+
+                let mut targets = Vec::new();
+                visit(build_info, target, &mut Vec::new(), &mut targets);
+
+                // Link dependencies after the targets that use them.
+                targets.reverse();
+                targets
+            }
+        };
+
+        for target in targets.iter().filter(|x| x.get_kind().is_linkable()) {
+            for include_directory in target.get_include_directories() {
+                local_library.add_include_directory(include_directory);
+            }
+
+            let Some(artifact) = target.get_artifacts().first() else {
+                continue;
+            };
+
+            // Shared libraries on Windows have the DLL and its import library as artifacts, which
+            // may be written to different directories.
+            for directory in target.get_artifacts().iter().filter_map(|x| x.parent()) {
+                local_library.add_library_directory(directory);
+                local_library.add_runtime_directory(directory);
+            }
+
+            if build_target.to_lowercase() != "all" {
+                local_library.link_target(get_library_name(artifact).as_str());
+            }
+        }
+
+        Ok(local_library)
+    }
+
+    /// Create a new `LocalLibrary` instance from a pkg-config package.
+    ///
+    /// Panics if the package could not be found, see `try_from_pkg_config`.
+    pub fn from_pkg_config(
+        name: &str,
+        version_req: &str,
+    ) -> LocalLibrary {
+        unwrap_or_panic(LocalLibrary::try_from_pkg_config(name, version_req))
+    }
+
+    /// Create a new `LocalLibrary` instance from a pkg-config package, returning an error if the
+    /// package could not be found or its version does not satisfy `version_req`.
+    ///
+    /// The `.pc` files are read directly, searching `PKG_CONFIG_PATH` followed by
+    /// `PKG_CONFIG_LIBDIR` or the default system locations. `PKG_CONFIG_SYSROOT_DIR` is prepended
+    /// to include and library directories, and each variable can be overridden per target the same
+    /// way as with the `pkg-config` crate.
+    ///
+    /// Libraries found in the package's library directories are added as link targets, everything
+    /// else is added as a system link target. `version_req` takes a comma separated list of
+    /// constraints such as `>= 1.2, < 2`, a bare version is treated as a minimum version and `*`
+    /// accepts any version.
+    pub fn try_from_pkg_config(
+        name: &str,
+        version_req: &str,
+    ) -> Result<LocalLibrary, Error> {
+
+        let package = pkg_config::probe(name, &VersionReq::parse(version_req)?)?;
+        let mut local_library = LocalLibrary::empty(package.prefix.as_path());
+        local_library.prebuilt = true;
+
+        local_library.version(package.version.as_str());
+
+        for include_directory in package.include_directories.iter() {
+            local_library.add_include_directory(include_directory);
+        }
+
+        for library_directory in package.library_directories.iter() {
+            local_library.add_library_directory(library_directory);
+        }
+
+        for library in package.libraries.iter() {
+            match pkg_config::is_local_library(&package, library)? {
+                true => local_library.link_target(library),
+                false => local_library.link_system_target(library),
+            };
+        }
+
+        for framework in package.frameworks.iter() {
+            local_library.link_target_with(framework, LinkKind::Framework);
+        }
+
+        for runtime_directory in DEFAULT_RUNTIME_DIRECTORIES {
+            local_library.add_runtime_directory(Path::new(runtime_directory));
+        }
+
+        Ok(local_library)
+    }
+
+    /// Create a new `LocalLibrary` instance from the metadata of a dependency that binds a library.
+    ///
+    /// Panics if the metadata could not be found, see `try_from_dependency`.
+    pub fn from_dependency(
+        links: &str,
+    ) -> LocalLibrary {
+        unwrap_or_panic(LocalLibrary::try_from_dependency(links))
+    }
+
+    /// Create a new `LocalLibrary` instance from the metadata of a dependency that binds a library,
+    /// returning an error if the metadata could not be found.
+    ///
+    /// Binding a library emits its install, include and library directories as `root`, `include`
+    /// and `lib` metadata. When the crate binding it sets `links`, cargo passes these to the build
+    /// scripts of crates depending on it as `DEP_<LINKS>_ROOT`, `DEP_<LINKS>_INCLUDE` and
+    /// `DEP_<LINKS>_LIB`. Each holds the directories of every library the dependency bound,
+    /// separated the same way as `PATH`, and the first root is used as the install directory.
+    ///
+    /// The library is already linked by the dependency, so no link targets are added. This is
+    /// meant for compiling code against its headers.
+    pub fn try_from_dependency(
+        links: &str,
+    ) -> Result<LocalLibrary, Error> {
+        let prefix = format!("DEP_{}_", links.to_uppercase().replace('-', "_"));
+
+        for suffix in ["ROOT", "INCLUDE", "LIB"] {
+            rerun_if_env_changed(format!("{}{}", prefix, suffix).as_str());
+        }
+
+        let root = env::var_os(format!("{}ROOT", prefix))
+            .and_then(|x| env::split_paths(&x).next())
+            .ok_or(Error::LibraryNotFound(
+                format!("{}ROOT is not set, does the dependency set `links = \"{}\"`?", prefix, links)
+            ))?;
+
+        let mut local_library = LocalLibrary::empty(root.as_path());
+
+        for include_directory in env::split_paths(&env::var_os(format!("{}INCLUDE", prefix)).unwrap_or_default()) {
+            local_library.add_include_directory(include_directory.as_path());
+        }
+
+        for library_directory in env::split_paths(&env::var_os(format!("{}LIB", prefix)).unwrap_or_default()) {
+            local_library.add_library_directory(library_directory.as_path());
+        }
+
+        Ok(local_library)
+    }
+
+    /// Create a new `LocalLibrary` instance from the exported targets of an installed CMake package.
+    ///
+    /// Panics if the package or one of the targets could not be found, see
+    /// `try_from_cmake_package`.
+    pub fn from_cmake_package(
+        install_directory: &Path,
+        package: &str,
+        targets: &[&str],
+    ) -> LocalLibrary {
+        unwrap_or_panic(LocalLibrary::try_from_cmake_package(install_directory, package, targets))
+    }
+
+    /// Create a new `LocalLibrary` instance from the exported targets of an installed CMake
+    /// package, returning an error if the package or one of the targets could not be found.
+    ///
+    /// The package config and targets files written by `install(EXPORT)`, usually found in
+    /// `lib/cmake/<package>`, are read to find the include directories, library files and
+    /// `INTERFACE_LINK_LIBRARIES` of each target. Dependencies are followed transitively, imported
+    /// targets from other packages are looked up in the same install directory and anything
+    /// outside of it is linked as a system target.
+    pub fn try_from_cmake_package(
+        install_directory: &Path,
+        package: &str,
+        targets: &[&str],
+    ) -> Result<LocalLibrary, Error> {
+
+        let package = cmake_package::read_package(install_directory, package, targets)?;
+        let mut local_library = LocalLibrary::empty(install_directory);
+
+        for include_directory in package.include_directories.iter() {
+            local_library.add_include_directory(include_directory);
+        }
+
+        for library_directory in package.library_directories.iter() {
+            local_library.add_library_directory(library_directory);
+        }
+
+        for target in package.link_targets.iter() {
+            local_library.link_target(target);
+        }
+
+        for target in package.system_link_targets.iter() {
+            local_library.link_system_target(target);
+        }
+
+        for framework in package.frameworks.iter() {
+            local_library.link_target_with(framework, LinkKind::Framework);
+        }
+
+        for runtime_directory in DEFAULT_RUNTIME_DIRECTORIES {
+            local_library.add_runtime_directory(Path::new(runtime_directory));
+        }
+
+        Ok(local_library)
+    }
+
+    fn empty(install_directory: &Path) -> LocalLibrary {
+        LocalLibrary {
+            install_directory: install_directory.into(),
+            version: None,
+            strict: true,
+            prebuilt: false,
+
+            rpath: RPath::default(),
+            link_modifiers: LinkModifiers::default(),
+            link_targets: Vec::new(),
+            system_link_targets: Vec::new(),
+
+            include_directories: Vec::new(),
+            library_directories: Vec::new(),
+            runtime_directories: Vec::new(),
+
+            dependencies: Vec::new(),
+        }
+    }
+
+    /// Set the version of the library.
+    ///
+    /// This is filled in automatically when the library comes from pkg-config.
+    pub fn version(
+        &mut self,
+        version: &str,
+    ) -> &mut LocalLibrary {
+        self.version = Some(version.to_string());
+        self
+    }
+
+    /// Set whether a link target that can not be found is an error.
+    ///
+    /// This defaults to `true`. When `false`, missing link targets are reported as warnings and
+    /// skipped.
+    pub fn strict(
+        &mut self,
+        strict: bool,
+    ) -> &mut LocalLibrary {
+        self.strict = strict;
+        self
+    }
+
+    /// Set the runtime search path added when shared libraries of this library are linked.
+    ///
+    /// This defaults to `RPath::Origin`, the directory shared libraries are copied to. The path
+    /// only applies to the binaries, tests and examples of the crate binding the library, other
+    /// crates can use `emit_rpath` in their own build script.
+    pub fn rpath(
+        &mut self,
+        rpath: RPath,
+    ) -> &mut LocalLibrary {
+        self.rpath = rpath;
+        self
     }
 
     /// Add a directory that will be searched for include files.
@@ -107,10 +446,31 @@ impl LocalLibrary {
         self
     }
 
+    /// Add a directory that will be searched for DLLs on Windows.
+    ///
+    /// The path should be relative to the installation directory. Import libraries are linked
+    /// from the library directories, while the DLLs they refer to are usually installed to `bin`
+    /// and copied to the target directory from there.
+    pub fn add_runtime_directory(
+        &mut self,
+        path: &Path,
+    ) -> &mut LocalLibrary {
+
+        // Check runtime directory exists
+        let runtime_directory = self.install_directory.join(path);
+        if runtime_directory.exists() && runtime_directory.is_dir() {
+            self.runtime_directories.push(runtime_directory)
+        }
+
+        self
+    }
+
     /// Add a target to link against.
     ///
-    /// Before linking, the crate will check if the library exists. If it finds a static and shared
-    /// library with the same name, it will always prefer the static library.
+    /// Before linking, the crate will check if the library exists. Library directories are
+    /// searched in the order they were added and the first one containing the library is used.
+    /// If it finds a static and shared library with the same name in that directory, it will
+    /// always prefer the static library.
     ///
     /// When linking against a shared library, the shared object will be copied to the target
     /// directory.
@@ -118,7 +478,50 @@ impl LocalLibrary {
         &mut self,
         target: &str,
     ) -> &mut LocalLibrary {
-        self.link_targets.push(target.to_string());
+        self.link_target_with(target, LinkKind::PreferStatic)
+    }
+
+    /// Add a target to link against, choosing how it is linked.
+    ///
+    /// Unlike `link_target`, only the type of library requested by `kind` is accepted. Frameworks
+    /// are searched for in the library directories and are otherwise assumed to be provided by the
+    /// system.
+    pub fn link_target_with(
+        &mut self,
+        target: &str,
+        kind: LinkKind,
+    ) -> &mut LocalLibrary {
+        self.link_target_with_modifiers(target, kind, &LinkModifiers::default())
+    }
+
+    /// Add a target to link against, choosing how it is linked and which rustc link modifiers are
+    /// used.
+    ///
+    /// Modifiers that are not set fall back to the ones given to `link_modifiers`. With
+    /// `verbatim`, the target is the full file name of the library, such as `libfoo.so.3`.
+    pub fn link_target_with_modifiers(
+        &mut self,
+        target: &str,
+        kind: LinkKind,
+        modifiers: &LinkModifiers,
+    ) -> &mut LocalLibrary {
+        self.link_targets.push(LinkTarget {
+            name: target.to_string(),
+            kind,
+            modifiers: *modifiers,
+        });
+        self
+    }
+
+    /// Set the rustc link modifiers used for every link target of this library.
+    ///
+    /// Modifiers given to `link_target_with_modifiers` take precedence. This does not apply to
+    /// dependencies or system link targets.
+    pub fn link_modifiers(
+        &mut self,
+        modifiers: &LinkModifiers,
+    ) -> &mut LocalLibrary {
+        self.link_modifiers = *modifiers;
         self
     }
 
@@ -136,12 +539,57 @@ impl LocalLibrary {
         self
     }
 
+    /// Add a library that this library depends on.
+    ///
+    /// When binding, dependencies are bound as well and their link targets are emitted after the
+    /// link targets of the libraries that use them, which is the order required when linking
+    /// static libraries. The dependency is copied, changes made to it afterwards are not seen.
+    ///
+    /// Libraries that depend on each other are reported with a warning and their static libraries
+    /// are linked again inside `-Wl,--start-group` and `-Wl,--end-group` so the cycle can be
+    /// resolved. These are linker arguments, which only apply to the binaries, tests and examples
+    /// of the crate binding the library. Linkers without groups, such as the Apple and MSVC
+    /// linkers, get the static libraries with `+whole-archive` instead.
+    pub fn depends_on(
+        &mut self,
+        library: &LocalLibrary,
+    ) -> &mut LocalLibrary {
+        self.dependencies.push(library.clone());
+        self
+    }
+
     /// Finalize the `LocalLibrary` configuration.
     pub fn get(&self) -> LocalLibrary {
         self.clone()
     }
 
-    pub (crate) fn get_link_targets(&self) -> &Vec<String> {
+    pub (crate) fn get_install_directory(&self) -> &PathBuf {
+        &self.install_directory
+    }
+
+    pub (crate) fn get_version(&self) -> &Option<String> {
+        &self.version
+    }
+
+    pub (crate) fn is_strict(&self) -> bool {
+        self.strict
+    }
+
+    /// Whether the library was built outside the build script, so changes to its files should
+    /// rerun the build script.
+    pub (crate) fn is_prebuilt(&self) -> bool {
+        self.prebuilt
+    }
+
+    pub (crate) fn get_rpath(&self) -> RPath {
+        self.rpath
+    }
+
+    pub (crate) fn get_link_modifiers(&self) -> &LinkModifiers {
+        &self.link_modifiers
+    }
+
+    pub (crate) fn get_link_targets(&self) -> &Vec<LinkTarget> {
         &self.link_targets
     }
 
@@ -156,4 +604,106 @@ impl LocalLibrary {
     pub (crate) fn get_library_directories(&self) -> &Vec<PathBuf> {
         &self.library_directories
     }
-}
\ No newline at end of file
+
+    pub (crate) fn get_runtime_directories(&self) -> &Vec<PathBuf> {
+        &self.runtime_directories
+    }
+
+    /// Human readable description used when reporting problems with the library.
+    pub (crate) fn describe(&self) -> String {
+        let link_targets = self.link_targets.iter()
+            .map(|x| x.name.as_str())
+            .collect::<Vec<&str>>();
+
+        format!("{:?} in {:?}", link_targets, self.install_directory)
+    }
+
+    /// Two libraries are the same if they link the same targets from the same location.
+    fn is_same_library(&self, other: &LocalLibrary) -> bool {
+        self.install_directory == other.install_directory
+            && self.link_targets == other.link_targets
+            && self.system_link_targets == other.system_link_targets
+    }
+
+    /// Sort this library and all of its transitive dependencies so that every library comes
+    /// before the libraries it depends on.
+    pub (crate) fn get_link_order(&self) -> LinkOrder<'_> {
+
+        fn visit<'a>(
+            library: &'a LocalLibrary,
+            stack: &mut Vec<&'a LocalLibrary>,
+            order: &mut LinkOrder<'a>,
+        ) {
+            if let Some(index) = stack.iter().position(|x| x.is_same_library(library)) {
+                order.cycles.push(stack[index..].to_vec());
+                return;
+            }
+
+            if order.libraries.iter().any(|x| x.is_same_library(library)) {
+                return;
+            }
+
+            stack.push(library);
+
+            for dependency in library.dependencies.iter() {
+                visit(dependency, stack, order);
+            }
+
+            stack.pop();
+            order.libraries.push(library);
+        }
+
+        let mut order = LinkOrder {
+            libraries: Vec::new(),
+            cycles: Vec::new(),
+        };
+
+        visit(self, &mut Vec::new(), &mut order);
+
+        // Dependencies were visited first, reverse so they are linked last.
+        order.libraries.reverse();
+        order
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn library(name: &str) -> LocalLibrary {
+        LocalLibrary::new(Path::new("/opt").join(name).as_path())
+            .link_system_target(name)
+            .get()
+    }
+
+    fn names(libraries: &[&LocalLibrary]) -> Vec<String> {
+        libraries.iter()
+            .map(|x| x.get_system_link_targets()[0].clone())
+            .collect()
+    }
+
+    #[test]
+    fn links_dependencies_after_their_dependents() {
+        let base = library("base");
+        let left = library("left").depends_on(&base).get();
+        let right = library("right").depends_on(&base).get();
+        let app = library("app").depends_on(&left).depends_on(&right).get();
+
+        let order = app.get_link_order();
+
+        assert_eq!(names(order.libraries.as_slice()), vec!["app", "right", "left", "base"]);
+        assert!(order.cycles.is_empty());
+    }
+
+    #[test]
+    fn reports_dependency_cycles() {
+        let a = library("a");
+        let b = library("b").depends_on(&a).get();
+        let a = library("a").depends_on(&b).get();
+
+        let order = a.get_link_order();
+
+        assert_eq!(names(order.libraries.as_slice()), vec!["a", "b"]);
+        assert_eq!(order.cycles.len(), 1);
+        assert_eq!(names(order.cycles[0].as_slice()), vec!["a", "b"]);
+    }
+}
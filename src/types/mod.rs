@@ -1,2 +1,6 @@
 pub mod local_library;
-pub mod cmake_builder;
\ No newline at end of file
+pub mod cmake_builder;
+pub mod library_resolver;
+pub mod build_info;
+pub mod link;
+pub mod clone_options;
\ No newline at end of file
@@ -1,76 +1,212 @@
 use std::env;
 use std::path::PathBuf;
-use crate::variables::Platform::{Linux, MacOS, Windows};
+use crate::commands::rerun_if_env_changed;
+use crate::error::Error;
+use crate::LIBRARY_NAME_PREFIX;
+use crate::variables::Platform::{Apple, Unix, Windows};
 
-#[derive(PartialEq)]
+/// Family of targets sharing the same library conventions.
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub (crate) enum Platform {
+    /// Targets using PE files, shared libraries are linked through import libraries.
     Windows,
-    Linux,
-    MacOS,
+
+    /// Apple targets using Mach-O files.
+    Apple,
+
+    /// Every other target, using Unix library names.
+    Unix,
 }
 
-pub (crate) fn platform() -> Platform {
-    let target = env::var("TARGET")
-        .unwrap();
+/// Operating systems loading ELF shared libraries through a dynamic loader that reads `DT_RUNPATH`
+/// and expands `$ORIGIN`.
+const ELF_LOADER_OPERATING_SYSTEMS: [&str; 10] = [
+    "linux",
+    "android",
+    "freebsd",
+    "netbsd",
+    "openbsd",
+    "dragonfly",
+    "solaris",
+    "illumos",
+    "hurd",
+    "haiku",
+];
+
+/// Target the crate is being built for, as described by the `CARGO_CFG_TARGET_*` variables cargo
+/// sets for build scripts.
+pub (crate) struct TargetInfo {
+    pub (crate) os: String,
+    pub (crate) env: String,
+    pub (crate) vendor: String,
+    pub (crate) family: String,
+}
 
-    if target.contains("windows") { return Windows }
-    else if target.contains("linux") { return Linux }
-    else if target.contains("apple-darwin") { return MacOS }
+pub (crate) fn target_info() -> Result<TargetInfo, Error> {
+    let os = cargo_env_var("CARGO_CFG_TARGET_OS")?;
 
-    panic!("Platform not supported: {}", target);
+    Ok(TargetInfo {
+        os,
+        env: env::var("CARGO_CFG_TARGET_ENV").unwrap_or_default(),
+        vendor: env::var("CARGO_CFG_TARGET_VENDOR").unwrap_or_default(),
+        family: env::var("CARGO_CFG_TARGET_FAMILY").unwrap_or_default(),
+    })
 }
 
-pub (crate) fn static_library_extension() -> &'static str {
-    match platform() {
-        Windows => ".lib",
-        Linux   => ".a",
-        MacOS   => ".a",
-    }
+pub (crate) fn platform() -> Result<Platform, Error> {
+    let target = target_info()?;
+
+    Ok(match target.os.as_str() {
+        "windows" | "uefi" | "cygwin" => Windows,
+        "macos" | "ios" | "tvos" | "watchos" | "visionos" => Apple,
+        _ if target.vendor == "apple" => Apple,
+        _ => Unix,
+    })
+}
+
+/// Whether libraries follow the MSVC convention of no prefix and `.lib` archives.
+pub (crate) fn uses_msvc_naming() -> Result<bool, Error> {
+    let target = target_info()?;
+
+    Ok(target.env == "msvc" || target.os == "uefi")
+}
+
+/// Whether shared libraries are found at runtime through the runtime search path of ELF files.
+pub (crate) fn uses_elf_loader() -> Result<bool, Error> {
+    let target = target_info()?;
+
+    Ok(ELF_LOADER_OPERATING_SYSTEMS.contains(&target.os.as_str()))
+}
+
+/// Whether the target is linked through a GNU-style compiler driver, which accepts linker
+/// arguments such as `-Wl,--start-group`.
+///
+/// Apple and MSVC linkers have no groups, WebAssembly and bare metal targets call the linker
+/// directly.
+pub (crate) fn uses_gnu_linker() -> Result<bool, Error> {
+    let target = target_info()?;
+
+    Ok(platform()? != Apple
+        && !uses_msvc_naming()?
+        && target.os != "none"
+        && !target.family.split(',').any(|x| x == "wasm"))
+}
+
+pub (crate) fn library_prefix() -> Result<&'static str, Error> {
+    Ok(match uses_msvc_naming()? {
+        true => "",
+        false => LIBRARY_NAME_PREFIX,
+    })
 }
 
-pub (crate) fn shared_library_extension() -> &'static str {
-    match platform() {
+pub (crate) fn static_library_extension() -> Result<&'static str, Error> {
+    Ok(match uses_msvc_naming()? {
+        true => ".lib",
+        false => ".a",
+    })
+}
+
+pub (crate) fn shared_library_extension() -> Result<&'static str, Error> {
+    Ok(match platform()? {
         Windows => ".dll",
-        Linux   => ".so",
-        MacOS   => ".dylib",
-    }
+        Apple   => ".dylib",
+        Unix    => ".so",
+    })
+}
+
+/// Read a variable cargo sets for build scripts.
+fn cargo_env_var(name: &str) -> Result<String, Error> {
+    env::var(name)
+        .map_err(|_| Error::InvalidEnvironment(format!("{} is not set, is this running in a build script?", name)))
 }
 
-pub (crate) fn out_directory() -> PathBuf {
-    PathBuf::from(
-        env::var("OUT_DIR")
-            .unwrap()
-    )
+pub (crate) fn out_directory() -> Result<PathBuf, Error> {
+    Ok(PathBuf::from(cargo_env_var("OUT_DIR")?))
 }
 
-pub (crate) fn get_profile() -> String {
-    env::var("PROFILE").unwrap()
+pub (crate) fn get_profile() -> Result<String, Error> {
+    cargo_env_var("PROFILE")
 }
 
 // Credits: https://github.com/Rust-SDL2/rust-sdl2/blob/master/sdl2-sys/build.rs#L388C1-L408C2
-pub (crate) fn target_directory() -> PathBuf {
+pub (crate) fn target_directory() -> Result<PathBuf, Error> {
 
     // Infer the top level cargo target dir from the OUT_DIR by searching
     // upwards until we get to $CARGO_TARGET_DIR/build/ (which is always one
     // level up from the deepest directory containing our package name)
-    let pkg_name = env::var("CARGO_PKG_NAME").unwrap();
-    let mut out_dir = out_directory();
-
-    loop {
-        {
-            let final_path_segment = out_dir.file_name().unwrap();
-            if final_path_segment.to_string_lossy().contains(&pkg_name) {
-                break;
-            }
-        }
-
-        if !out_dir.pop() {
-            panic!("Malformed build path: {}", out_dir.to_string_lossy());
-        }
+    let pkg_name = cargo_env_var("CARGO_PKG_NAME")?;
+    let out_dir = out_directory()?;
+
+    let package_directory = out_dir.ancestors()
+        .find(|x| x.file_name().is_some_and(|x| x.to_string_lossy().contains(&pkg_name)))
+        .ok_or(Error::InvalidEnvironment(format!("malformed build path: {}", out_dir.to_string_lossy())))?;
+
+    package_directory.parent()
+        .and_then(|x| x.parent())
+        .filter(|x| x.parent().is_some())
+        .map(|x| x.to_path_buf())
+        .ok_or(Error::InvalidEnvironment(format!("malformed build path: {}", out_dir.to_string_lossy())))
+}
+
+/// Directory shared by every profile of the cargo target directory, where sources are fetched to.
+pub (crate) fn shared_target_directory() -> Result<PathBuf, Error> {
+    Ok(target_directory()?.parent().unwrap().to_path_buf())
+}
+
+/// Directory builds are shared through, set with `BIND_BUILDER_CACHE_DIR`.
+pub (crate) fn cache_directory() -> Option<PathBuf> {
+    rerun_if_env_changed("BIND_BUILDER_CACHE_DIR");
+
+    env::var_os("BIND_BUILDER_CACHE_DIR")
+        .filter(|x| !x.is_empty())
+        .map(PathBuf::from)
+}
+
+pub (crate) fn target() -> Result<String, Error> {
+    cargo_env_var("TARGET")
+}
+
+pub (crate) fn host() -> Result<String, Error> {
+    env::var("HOST").or_else(|_| target())
+}
+
+/// Names an environment variable may be overridden with per target, using the same lookup order
+/// as the `cc`, `cmake` and `pkg-config` crates.
+///
+/// `<NAME>_<TARGET>`, `<NAME>_<TARGET_WITH_UNDERSCORES>`, `<HOST|TARGET>_<NAME>`, `<NAME>`.
+pub (crate) fn target_env_var_names(name: &str) -> Result<Vec<String>, Error> {
+    let target = target()?;
+    let kind = if host()? == target { "HOST" } else { "TARGET" };
+
+    Ok(vec![
+        format!("{}_{}", name, target),
+        format!("{}_{}", name, target.replace('-', "_")),
+        format!("{}_{}", kind, name),
+        name.to_string(),
+    ])
+}
+
+/// Read an environment variable that may be overridden per target, see `target_env_var_names`.
+///
+/// Every name is registered with cargo so that changing any of them reruns the build script.
+pub (crate) fn target_env_var(name: &str) -> Result<Option<String>, Error> {
+    let names = target_env_var_names(name)?;
+
+    for name in names.iter() {
+        rerun_if_env_changed(name);
     }
 
-    out_dir.pop();
-    out_dir.pop();
+    Ok(names.iter().find_map(|x| env::var(x).ok()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    out_dir
-}
\ No newline at end of file
+    #[test]
+    fn requires_cargo_target_variables() {
+        // Cargo only sets `CARGO_CFG_TARGET_*` for build scripts, not for tests.
+        assert!(matches!(target_info(), Err(Error::InvalidEnvironment(_))));
+        assert!(platform().is_err());
+    }
+}
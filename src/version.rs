@@ -0,0 +1,188 @@
+use std::cmp::Ordering;
+use crate::error::Error;
+
+#[derive(Clone, Copy, PartialEq)]
+enum Operator {
+    Equal,
+    NotEqual,
+    Greater,
+    GreaterEqual,
+    Less,
+    LessEqual,
+}
+
+/// Compare two version strings the same way pkg-config does.
+///
+/// Versions are split into runs of digits and letters, digit runs compare numerically, letter
+/// runs compare lexically and a digit run is always newer than a letter run.
+pub (crate) fn compare_versions(a: &str, b: &str) -> Ordering {
+    let mut a = a.trim();
+    let mut b = b.trim();
+
+    loop {
+        a = a.trim_start_matches(|c: char| !c.is_ascii_alphanumeric());
+        b = b.trim_start_matches(|c: char| !c.is_ascii_alphanumeric());
+
+        if a.is_empty() || b.is_empty() {
+            return a.len().cmp(&b.len());
+        }
+
+        let numeric = a.starts_with(|c: char| c.is_ascii_digit());
+
+        let split = |x: &str| -> usize {
+            x.find(|c: char| match numeric {
+                true => !c.is_ascii_digit(),
+                false => !c.is_ascii_alphabetic(),
+            }).unwrap_or(x.len())
+        };
+
+        let (segment_a, rest_a) = a.split_at(split(a));
+        let (segment_b, rest_b) = b.split_at(split(b));
+
+        // Segments of different kinds, numbers are considered newer.
+        if segment_b.is_empty() {
+            return match numeric {
+                true => Ordering::Greater,
+                false => Ordering::Less,
+            };
+        }
+
+        let ordering = match numeric {
+            true => {
+                let segment_a = segment_a.trim_start_matches('0');
+                let segment_b = segment_b.trim_start_matches('0');
+
+                segment_a.len().cmp(&segment_b.len())
+                    .then_with(|| segment_a.cmp(segment_b))
+            },
+            false => segment_a.cmp(segment_b),
+        };
+
+        if ordering != Ordering::Equal {
+            return ordering;
+        }
+
+        a = rest_a;
+        b = rest_b;
+    }
+}
+
+/// A set of version constraints such as `>= 1.2, < 2`.
+///
+/// An empty requirement or `*` matches any version. A version without an operator is treated as
+/// a minimum version, the same as pkg-config's `--atleast-version`.
+#[derive(Clone)]
+pub (crate) struct VersionReq {
+    source: String,
+    comparators: Vec<(Operator, String)>,
+}
+
+impl VersionReq {
+
+    pub (crate) fn any() -> VersionReq {
+        VersionReq {
+            source: "*".to_string(),
+            comparators: Vec::new(),
+        }
+    }
+
+    pub (crate) fn parse(requirement: &str) -> Result<VersionReq, Error> {
+        let mut comparators = Vec::new();
+
+        for comparator in requirement.split(',').map(|x| x.trim()) {
+            if comparator.is_empty() || comparator == "*" {
+                continue;
+            }
+
+            let (operator, version) = [
+                (">=", Operator::GreaterEqual),
+                ("<=", Operator::LessEqual),
+                ("!=", Operator::NotEqual),
+                ("==", Operator::Equal),
+                (">", Operator::Greater),
+                ("<", Operator::Less),
+                ("=", Operator::Equal),
+            ]
+                .iter()
+                .find_map(|(prefix, operator)| comparator.strip_prefix(prefix).map(|x| (*operator, x)))
+                .unwrap_or((Operator::GreaterEqual, comparator));
+
+            let version = version.trim();
+
+            if version.is_empty() || version.contains(char::is_whitespace) {
+                return Err(Error::InvalidVersion(requirement.to_string()));
+            }
+
+            comparators.push((operator, version.to_string()));
+        }
+
+        Ok(VersionReq {
+            source: requirement.trim().to_string(),
+            comparators,
+        })
+    }
+
+    pub (crate) fn matches(&self, version: &str) -> bool {
+        self.comparators.iter().all(|(operator, required)| {
+            let ordering = compare_versions(version, required);
+
+            match operator {
+                Operator::Equal => ordering == Ordering::Equal,
+                Operator::NotEqual => ordering != Ordering::Equal,
+                Operator::Greater => ordering == Ordering::Greater,
+                Operator::GreaterEqual => ordering != Ordering::Less,
+                Operator::Less => ordering == Ordering::Less,
+                Operator::LessEqual => ordering != Ordering::Greater,
+            }
+        })
+    }
+
+    pub (crate) fn as_str(&self) -> &str {
+        self.source.as_str()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn orders_versions() {
+        assert_eq!(compare_versions("1.10", "1.9"), Ordering::Greater);
+        assert_eq!(compare_versions("1.0", "1.0.1"), Ordering::Less);
+        assert_eq!(compare_versions("1.01", "1.1"), Ordering::Equal);
+        assert_eq!(compare_versions("2.0", "2_0"), Ordering::Equal);
+        assert_eq!(compare_versions(" 1.2 ", "1.2"), Ordering::Equal);
+        assert_eq!(compare_versions("1.0a", "1.0"), Ordering::Greater);
+        assert_eq!(compare_versions("1.0a", "1.0b"), Ordering::Less);
+        assert_eq!(compare_versions("1.0.1", "1.0a"), Ordering::Greater);
+        assert_eq!(compare_versions("10", "9"), Ordering::Greater);
+        assert_eq!(compare_versions("12345678901234567890", "12345678901234567889"), Ordering::Greater);
+    }
+
+    #[test]
+    fn matches_requirements() {
+        let requirement = VersionReq::parse(">= 1.2, < 2").unwrap();
+
+        assert!(requirement.matches("1.2"));
+        assert!(requirement.matches("1.10"));
+        assert!(!requirement.matches("1.1"));
+        assert!(!requirement.matches("2.0"));
+        assert_eq!(requirement.as_str(), ">= 1.2, < 2");
+
+        assert!(VersionReq::parse("1.2").unwrap().matches("1.3"));
+        assert!(!VersionReq::parse("1.2").unwrap().matches("1.1"));
+        assert!(VersionReq::parse("= 1.2").unwrap().matches("1.2"));
+        assert!(!VersionReq::parse("!= 1.2").unwrap().matches("1.2"));
+        assert!(VersionReq::parse("<= 1.2").unwrap().matches("1.2"));
+        assert!(!VersionReq::parse("> 1.2").unwrap().matches("1.2"));
+        assert!(VersionReq::parse("*").unwrap().matches("0"));
+        assert!(VersionReq::any().matches("0"));
+    }
+
+    #[test]
+    fn rejects_invalid_requirements() {
+        assert!(VersionReq::parse(">=").is_err());
+        assert!(VersionReq::parse(">= 1 2").is_err());
+    }
+}